@@ -0,0 +1,159 @@
+use std::{io, path::Path, str::FromStr};
+
+/// SELinux security context requested with `-Z` or `--context`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Context {
+    /// `-Z` without a value: the context the loaded policy assigns to the path.
+    Default,
+    /// `--context=CTX`: an explicit context such as `system_u:object_r:tmp_t:s0`.
+    Explicit(String),
+    /// `--context=type=httpd_sys_content_t`: the default context with the given components
+    /// replaced.
+    Components(Vec<(ContextComponent, String)>),
+}
+
+/// A field of a `user:role:type:range` security context, as named by `--context=KEY=VALUE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextComponent {
+    User,
+    Role,
+    Type,
+    Range,
+}
+
+/// The SELinux calls behind [`Context::apply_with`]. The real one is only built with the
+/// `selinux` feature; tests can supply their own to check labeling without an SELinux system.
+pub trait ContextBackend {
+    /// Labels `path` with the context the loaded policy assigns to it.
+    fn set_default(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns the current context of `path`.
+    fn get(&self, path: &Path) -> io::Result<String>;
+
+    /// Labels `path` with `context`.
+    fn set(&self, path: &Path, context: &str) -> io::Result<()>;
+}
+
+impl Context {
+    /// Labels `path` with this context through `backend`. For [`Context::Components`] the
+    /// path is first given its default context, which is then read back and relabeled with
+    /// the requested components replaced.
+    pub fn apply_with(&self, backend: &dyn ContextBackend, path: &Path) -> io::Result<()> {
+        match self {
+            Context::Default => backend.set_default(path),
+            Context::Explicit(context) => backend.set(path, context),
+            Context::Components(components) => {
+                backend.set_default(path)?;
+                let context = substitute(&backend.get(path)?, components)?;
+                backend.set(path, &context)
+            }
+        }
+    }
+}
+
+/// Replaces `components` in `context`. The range is everything after the third colon, since
+/// it may contain colons itself, as in `s0:c0.c1023`; a context without one gains it.
+fn substitute(context: &str, components: &[(ContextComponent, String)]) -> io::Result<String> {
+    let mut fields: Vec<&str> = context.splitn(4, ':').collect();
+    if fields.len() < 3 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed security context '{context}'"),
+        ));
+    }
+    for (component, value) in components {
+        let index = match component {
+            ContextComponent::User => 0,
+            ContextComponent::Role => 1,
+            ContextComponent::Type => 2,
+            ContextComponent::Range => 3,
+        };
+        match fields.get_mut(index) {
+            Some(field) => *field = value,
+            None => fields.push(value),
+        }
+    }
+    Ok(fields.join(":"))
+}
+
+impl FromStr for Context {
+    type Err = String;
+
+    /// An empty string is `-Z`'s default context. `KEY=VALUE` pairs separated by commas,
+    /// with keys `user`, `role`, `type` and `range`, override those components of it;
+    /// anything else is a full context.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Context::Default);
+        }
+        if !s.contains('=') {
+            return Ok(Context::Explicit(s.to_string()));
+        }
+
+        s.split(',')
+            .map(|pair| {
+                let (key, value) = pair
+                    .split_once('=')
+                    .filter(|(_, value)| !value.is_empty())
+                    .ok_or_else(|| format!("invalid context component: '{pair}'"))?;
+                let component = match key {
+                    "user" => ContextComponent::User,
+                    "role" => ContextComponent::Role,
+                    "type" => ContextComponent::Type,
+                    "range" => ContextComponent::Range,
+                    _ => return Err(format!("unknown context component: '{key}'")),
+                };
+                Ok((component, value.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Context::Components)
+    }
+}
+
+/// [`ContextBackend`] backed by libselinux.
+#[cfg(all(feature = "selinux", target_os = "linux"))]
+struct Selinux;
+
+#[cfg(all(feature = "selinux", target_os = "linux"))]
+impl ContextBackend for Selinux {
+    fn set_default(&self, path: &Path) -> io::Result<()> {
+        selinux::SecurityContext::set_default_for_path(path).map_err(io::Error::other)
+    }
+
+    fn get(&self, path: &Path) -> io::Result<String> {
+        let unlabeled = || io::Error::new(io::ErrorKind::NotFound, "no security context");
+        let context = selinux::SecurityContext::of_path(path, false, false)
+            .map_err(io::Error::other)?
+            .ok_or_else(unlabeled)?;
+        let context = context
+            .to_c_string()
+            .map_err(io::Error::other)?
+            .ok_or_else(unlabeled)?;
+        Ok(context.to_string_lossy().into_owned())
+    }
+
+    fn set(&self, path: &Path, context: &str) -> io::Result<()> {
+        let context = std::ffi::CString::new(context)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        selinux::SecurityContext::from_c_str(&context, false)
+            .set_for_path(path, false, false)
+            .map_err(io::Error::other)
+    }
+}
+
+/// Labels `path` with `context`.
+///
+/// Only available on Linux builds with the `selinux` feature; everywhere else this returns an
+/// `Unsupported` error so that `-Z` fails loudly instead of silently doing nothing.
+#[cfg(all(feature = "selinux", target_os = "linux"))]
+pub(crate) fn apply(path: &Path, context: &Context) -> io::Result<()> {
+    context.apply_with(&Selinux, path)
+}
+
+#[cfg(not(all(feature = "selinux", target_os = "linux")))]
+pub(crate) fn apply(_path: &Path, _context: &Context) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SELinux support is not available in this build",
+    ))
+}
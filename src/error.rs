@@ -0,0 +1,188 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use crate::{quote, quote_bytes};
+
+/// Errors returned by [`run`](crate::run), [`create_directory`](crate::create_directory) and
+/// [`read_config`](crate::read_config).
+///
+/// The `Display` output is the plain underlying message (e.g. `File exists (os error 17)`);
+/// callers add the directory name themselves.
+#[derive(Debug)]
+pub enum MkdirrError {
+    /// A mode could not be parsed.
+    InvalidMode(String),
+    /// The directory already exists.
+    AlreadyExists(io::Error),
+    /// A parent of the directory does not exist.
+    ParentMissing(io::Error),
+    /// A component of the path exists but is not a directory.
+    NotADirectory(PathBuf),
+    /// Under `--strict-parents`, the directory's parent, which does not exist.
+    MissingParent(PathBuf),
+    /// Any other I/O failure, such as a permission error.
+    Io(io::Error),
+    /// Creating a missing parent under `-p` failed; holds that parent rather than the
+    /// requested directory.
+    Component(PathBuf, io::Error),
+    /// A directory name is not valid UTF-8; holds its bytes, as read from `--stdin` or
+    /// `--from-file` or in the platform's encoding of a path.
+    InvalidUtf8(Vec<u8>),
+    /// A failure that is only described by its message, e.g. an unreadable `--from-file`.
+    Other(String),
+}
+
+impl MkdirrError {
+    /// Classifies an error returned while creating a directory.
+    pub(crate) fn from_create(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::AlreadyExists => MkdirrError::AlreadyExists(e),
+            io::ErrorKind::NotFound => MkdirrError::ParentMissing(e),
+            _ => MkdirrError::Io(e),
+        }
+    }
+}
+
+/// Errors that can be printed without the ` (os error N)` suffix std appends, as GNU tools
+/// print them.
+pub(crate) trait PlainMessage: fmt::Display {
+    fn plain_message(&self) -> String;
+}
+
+impl PlainMessage for io::Error {
+    fn plain_message(&self) -> String {
+        let message = self.to_string();
+        match self.raw_os_error() {
+            Some(code) => message
+                .strip_suffix(&format!(" (os error {code})"))
+                .map(String::from)
+                .unwrap_or(message),
+            None => message,
+        }
+    }
+}
+
+impl PlainMessage for MkdirrError {
+    fn plain_message(&self) -> String {
+        match self {
+            MkdirrError::AlreadyExists(e)
+            | MkdirrError::ParentMissing(e)
+            | MkdirrError::Io(e)
+            | MkdirrError::Component(_, e) => e.plain_message(),
+            MkdirrError::InvalidMode(_)
+            | MkdirrError::NotADirectory(_)
+            | MkdirrError::MissingParent(_)
+            | MkdirrError::InvalidUtf8(_)
+            | MkdirrError::Other(_) => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MkdirrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MkdirrError::InvalidMode(msg) | MkdirrError::Other(msg) => write!(f, "{msg}"),
+            MkdirrError::NotADirectory(path) => {
+                write!(f, "{} is not a directory", quote(path))
+            }
+            MkdirrError::MissingParent(path) => {
+                write!(f, "parent {} does not exist", quote(path))
+            }
+            MkdirrError::InvalidUtf8(name) => {
+                write!(f, "invalid UTF-8 in directory name {}", quote_bytes(name))
+            }
+            MkdirrError::AlreadyExists(e)
+            | MkdirrError::ParentMissing(e)
+            | MkdirrError::Io(e)
+            | MkdirrError::Component(_, e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for MkdirrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MkdirrError::AlreadyExists(e)
+            | MkdirrError::ParentMissing(e)
+            | MkdirrError::Io(e)
+            | MkdirrError::Component(_, e) => Some(e),
+            MkdirrError::InvalidMode(_)
+            | MkdirrError::NotADirectory(_)
+            | MkdirrError::MissingParent(_)
+            | MkdirrError::InvalidUtf8(_)
+            | MkdirrError::Other(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MkdirrError {
+    fn from(e: io::Error) -> Self {
+        MkdirrError::Io(e)
+    }
+}
+
+impl From<String> for MkdirrError {
+    fn from(msg: String) -> Self {
+        MkdirrError::Other(msg)
+    }
+}
+
+impl From<serde_json::Error> for MkdirrError {
+    fn from(e: serde_json::Error) -> Self {
+        MkdirrError::Io(e.into())
+    }
+}
+
+/// Why a mode could not be parsed by [`Mode`](crate::Mode)'s `FromStr` or
+/// [`Mode::parse_as`](crate::Mode::parse_as). The `Display` output is the message `-m` reports;
+/// clause positions are only in the `index` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModeParseError {
+    /// The mode is empty or only whitespace.
+    Empty,
+    /// The clause at 1-based position `index` has no `+`, `-` or `=` operator, or for word
+    /// modes no `:`.
+    BadClause { clause: String, index: usize },
+    /// A relative clause names a class other than `u`, `g`, `o` and `a`.
+    InvalidClass(char),
+    /// `clause`, at 1-based position `index`, contains `perm`, which is not a permission letter
+    /// or, like `s` in `o+s`, does not apply to the clause's classes.
+    InvalidPerm {
+        clause: String,
+        perm: char,
+        index: usize,
+    },
+    /// An `=` clause names an unknown class, or a word clause such as
+    /// `user:read` names an unknown class or permission.
+    UnknownClassOrPerm(String),
+    /// A numeric mode is out of range, or a plain mode has a letter other than `rwx`.
+    InvalidMode(String),
+    /// `--mode-format octal` was given a symbolic mode.
+    ExpectedOctal(String),
+    /// `--mode-format symbolic` was given a numeric mode.
+    ExpectedSymbolic(String),
+}
+
+impl fmt::Display for ModeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModeParseError::Empty => write!(f, "Mode must be defined"),
+            ModeParseError::BadClause { clause, .. } => {
+                write!(f, "Invalid permission format: '{clause}'")
+            }
+            ModeParseError::InvalidClass(class) => write!(f, "Unknown class: {class}"),
+            ModeParseError::InvalidPerm { clause, .. } => {
+                write!(f, "Invalid permissions in: {clause}")
+            }
+            ModeParseError::UnknownClassOrPerm(clause) => {
+                write!(f, "Unknown class or perm: {clause}")
+            }
+            ModeParseError::InvalidMode(mode) => write!(f, "Invalid mode: {mode}"),
+            ModeParseError::ExpectedOctal(mode) => write!(f, "Expected an octal mode: {mode}"),
+            ModeParseError::ExpectedSymbolic(mode) => {
+                write!(f, "Expected a symbolic mode: {mode}")
+            }
+        }
+    }
+}
+
+impl Error for ModeParseError {}
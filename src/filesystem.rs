@@ -0,0 +1,102 @@
+use std::{
+    fmt,
+    fs::{self, Metadata, Permissions},
+    io,
+    path::Path,
+    sync::Arc,
+};
+
+#[cfg(unix)]
+use std::{fs::DirBuilder, os::unix::fs::DirBuilderExt};
+
+/// The filesystem calls `mkdirr` makes to create, move aside and remove directories and set
+/// their modes.
+///
+/// [`RealFs`] is used unless [`Config::with_fs`](crate::Config::with_fs) or
+/// [`create_directory_with`](crate::create_directory_with) supplies another implementation,
+/// typically one that wraps `RealFs` and fails chosen calls so error handling can be tested
+/// without root or a full disk. Checks for whether a path exists are not routed through it.
+pub trait Fs: fmt::Debug + Send + Sync {
+    /// Creates the single directory `path`, like `mkdir(2)`. A `mode` is the permission bits
+    /// to create it with, before the umask; platforms without Unix modes ignore it.
+    fn create_dir(&self, path: &Path, mode: Option<u32>) -> io::Result<()>;
+
+    /// Sets the permissions of `path`, like [`fs::set_permissions`].
+    fn set_permissions(&self, path: &Path, perms: Permissions) -> io::Result<()>;
+
+    /// Returns the metadata of `path`, following symlinks, like [`fs::metadata`].
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Creates `path` and any missing parents, like [`fs::create_dir_all`].
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Renames `from` to `to`, like [`fs::rename`]; `--backup` uses it to move a file aside.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Removes the empty directory `path`, like [`fs::remove_dir`]; `--transaction` uses it
+    /// to roll back.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns the inodes free on the filesystem holding `path` for `--check-space`, or
+    /// `None` if that is unknown.
+    fn free_inodes(&self, path: &Path) -> Option<u64>;
+}
+
+/// [`Fs`] backed by `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    #[cfg(unix)]
+    fn create_dir(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        match mode {
+            Some(mode) => DirBuilder::new().mode(mode & 0o777).create(path),
+            None => fs::create_dir(path),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn create_dir(&self, path: &Path, _mode: Option<u32>) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn set_permissions(&self, path: &Path, perms: Permissions) -> io::Result<()> {
+        fs::set_permissions(path, perms)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::metadata(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    #[cfg(unix)]
+    fn free_inodes(&self, path: &Path) -> Option<u64> {
+        crate::space::free_inodes(path)
+    }
+
+    #[cfg(not(unix))]
+    fn free_inodes(&self, _path: &Path) -> Option<u64> {
+        None
+    }
+}
+
+/// The [`Fs`] a [`Config`](crate::Config) uses, [`RealFs`] by default.
+#[derive(Debug, Clone)]
+pub(crate) struct FsHandle(pub(crate) Arc<dyn Fs>);
+
+impl Default for FsHandle {
+    fn default() -> Self {
+        FsHandle(Arc::new(RealFs))
+    }
+}
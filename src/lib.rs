@@ -1,223 +1,2047 @@
-use clap::{self, ArgAction, Command, arg, value_parser};
+//! A Rust implementation of `mkdir`.
+//!
+//! Besides the `mkdirr` binary, the crate can be embedded: build a [`Config`] and hand it to
+//! [`run`], which is the intended entry point for library users.
+//!
+//! ```no_run
+//! use mkdirr::{Config, run};
+//!
+//! let config = Config::new(vec!["logs/app".to_string()])
+//!     .with_parents(true)
+//!     .with_mode("750".parse()?);
+//! let exit_status = run(&config)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use clap::{self, Arg, ArgAction, Command, arg, value_parser};
+use clap_complete::{Shell, generate};
+use clap_mangen::Man;
+use indicatif::ProgressBar;
+use log::{debug, info};
+use serde::Serialize;
 use std::{
-    error::Error,
-    fs::{Permissions, create_dir, create_dir_all, set_permissions},
-    os::unix::fs::PermissionsExt,
-    path::Path,
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsStr,
+    fmt,
+    fs::{self, Permissions, metadata},
+    io::{self, IsTerminal, Read},
+    path::{Component, Path, PathBuf},
     process,
-    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-pub type MyResult<T> = Result<T, Box<dyn Error>>;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 
-#[derive(Debug, Clone, Default)]
-struct Mode {
-    user_read: bool,
-    user_write: bool,
-    user_execute: bool,
-    group_read: bool,
-    group_write: bool,
-    group_execute: bool,
-    other_read: bool,
-    other_write: bool,
-    other_execute: bool,
-}
+mod context;
+mod error;
+mod filesystem;
+mod mode;
+mod owner;
+mod space;
 
-impl FromStr for Mode {
-    type Err = String;
+pub use context::{Context, ContextBackend, ContextComponent};
+use error::PlainMessage;
+pub use error::{MkdirrError, ModeParseError};
+use filesystem::FsHandle;
+pub use filesystem::{Fs, RealFs};
+use mode::mode_bits;
+pub use mode::{Mode, ModeFormat};
+pub use owner::Owner;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let str = s.to_lowercase();
-        let mut mode = Mode::default();
+/// Exit status when every directory was created.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit status when one or more directories could not be created.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status for invalid arguments, matching clap's own usage errors.
+pub const EXIT_USAGE: i32 = 2;
 
-        if str.is_empty() {
-            return Err(format!("Mode must be defined"));
-        }
+pub type MyResult<T> = Result<T, MkdirrError>;
 
-        if str.contains("=") {
-            for group_perms in s.split(",") {
-                let (class, perms) = group_perms
-                    .split_once("=")
-                    .ok_or_else(|| format!("Invalid permission format: '{}'", group_perms))?;
+/// Returns the umask of the current process.
+///
+/// `umask(2)` can only be read by setting it, so the previous value is restored right away.
+#[cfg(unix)]
+pub fn current_umask() -> u32 {
+    // SAFETY: umask has no preconditions and cannot fail.
+    let mask = unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask
+    };
 
-                if perms.chars().any(|c| !"rwx".contains(c)) {
-                    return Err(format!("Invalid permissions in: {}", group_perms));
-                }
+    mask as u32
+}
 
-                for perm in perms.chars() {
-                    match (class, perm) {
-                        ("u", 'r') => mode.user_read = true,
-                        ("u", 'w') => mode.user_write = true,
-                        ("u", 'x') => mode.user_execute = true,
-                        ("g", 'r') => mode.group_read = true,
-                        ("g", 'w') => mode.group_write = true,
-                        ("g", 'x') => mode.group_execute = true,
-                        ("o", 'r') => mode.other_read = true,
-                        ("o", 'w') => mode.other_write = true,
-                        ("o", 'x') => mode.other_execute = true,
-                        _ => return Err(format!("Unknown class or perm: {}={}", class, perm)),
-                    }
-                }
-            }
+/// Other platforms have no umask; new directories are treated as `0o777`.
+#[cfg(not(unix))]
+pub fn current_umask() -> u32 {
+    0
+}
 
-            Ok(mode)
-        } else {
-            if s.chars().any(|c| !"rwx".contains(c)) {
-                return Err(format!("Invalid mode: {}", s));
-            }
-
-            let read = s.contains('r');
-            let write = s.contains('w');
-            let exec = s.contains('x');
-
-            Ok(Mode {
-                user_read: read,
-                user_write: write,
-                user_execute: exec,
-                group_read: read,
-                group_write: write,
-                group_execute: exec,
-                other_read: read,
-                other_write: write,
-                other_execute: exec,
-            })
-        }
+/// Sets the process umask for `--umask` and restores the previous one when dropped.
+#[cfg(unix)]
+struct UmaskGuard(libc::mode_t);
+
+#[cfg(unix)]
+impl UmaskGuard {
+    fn set(umask: u32) -> Self {
+        // SAFETY: umask has no preconditions and cannot fail.
+        UmaskGuard(unsafe { libc::umask(umask as libc::mode_t) })
     }
 }
 
-impl From<&Mode> for Permissions {
-    fn from(value: &Mode) -> Self {
-        let mut bits = 0;
-
-        if value.user_read {
-            bits |= 0o400;
-        }
-        if value.user_write {
-            bits |= 0o200;
-        }
-        if value.user_execute {
-            bits |= 0o100;
-        }
-        if value.group_read {
-            bits |= 0o040;
-        }
-        if value.group_write {
-            bits |= 0o020;
-        }
-        if value.group_execute {
-            bits |= 0o010;
-        }
-        if value.other_read {
-            bits |= 0o004;
-        }
-        if value.other_write {
-            bits |= 0o002;
-        }
-        if value.other_execute {
-            bits |= 0o001;
+#[cfg(unix)]
+impl Drop for UmaskGuard {
+    fn drop(&mut self) {
+        // SAFETY: as in `UmaskGuard::set`.
+        unsafe {
+            libc::umask(self.0);
         }
+    }
+}
 
-        PermissionsExt::from_mode(bits)
+/// Other platforms have no umask to override.
+#[cfg(not(unix))]
+struct UmaskGuard;
+
+#[cfg(not(unix))]
+impl UmaskGuard {
+    fn set(_umask: u32) -> Self {
+        UmaskGuard
     }
 }
 
-#[derive(Debug)]
+/// Parses a `--umask` value: up to four octal digits, at most `0777`.
+fn parse_umask(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|&umask| value.len() <= 4 && umask <= 0o777)
+        .ok_or_else(|| format!("invalid umask '{value}'"))
+}
+
+/// Options for a single invocation, built either from the command line by [`read_config`]
+/// or programmatically with [`Config::new`] and the `with_*` setters.
+#[derive(Debug, Default)]
 pub struct Config {
     dir_name: Vec<String>,
     parents: bool,
-    verbose: bool,
+    verbose: u8,
+    verbose_stderr: bool,
+    timestamps: bool,
+    null: bool,
     mode: Option<Mode>,
+    umask: Option<u32>,
+    mode_format: ModeFormat,
+    per_arg_mode: bool,
+    dry_run: bool,
+    diff: bool,
+    reference: Option<PathBuf>,
+    ignore_existing: bool,
+    no_clobber_mode: bool,
+    recursive: bool,
+    reconcile: bool,
+    strict_parents: bool,
+    backup: bool,
+    context: Option<Context>,
+    owner: Option<Owner>,
+    format: OutputFormat,
+    total: bool,
+    report_depth: bool,
+    tree: bool,
+    report_noop: bool,
+    progress: bool,
+    check_space: bool,
+    max_dirs: Option<usize>,
+    jobs: usize,
+    posixly_correct: bool,
+    quiet: bool,
+    exit_on_error: bool,
+    transaction: bool,
+    base: Option<PathBuf>,
+    root: Option<PathBuf>,
+    fs: FsHandle,
 }
 
-pub fn read_config() -> MyResult<Config> {
-    let app = Command::new("mkdirr")
+/// How results are reported on standard output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `created directory '...'` lines under `-v`.
+    #[default]
+    Human,
+    /// A single JSON array with one object per requested directory, printed at the end.
+    Json,
+    /// The objects of [`OutputFormat::Json`], one per line, each printed as soon as its
+    /// directory has been processed. With `--jobs` they are held back until every directory
+    /// is done and then printed in argument order, like the other output.
+    JsonLines,
+}
+
+impl Config {
+    /// Creates a config for `dirs` with every option off, as if no flags were given.
+    pub fn new(dirs: Vec<String>) -> Self {
+        Config {
+            dir_name: dirs,
+            ..Config::default()
+        }
+    }
+
+    /// Like `-p`: create missing parents and accept directories that already exist.
+    pub fn with_parents(mut self, parents: bool) -> Self {
+        self.parents = parents;
+        self
+    }
+
+    /// Like `-v`: print a message for each created directory.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose.into();
+        self
+    }
+
+    /// Like repeating `-v`: `1` prints created directories, `2` also prints existing ones that
+    /// `-p` or `--ignore-existing` skipped.
+    pub fn with_verbosity(mut self, level: u8) -> Self {
+        self.verbose = level;
+        self
+    }
+
+    /// Like `--verbose-stderr`: print the `-v` messages to stderr instead of stdout.
+    pub fn with_verbose_stderr(mut self, verbose_stderr: bool) -> Self {
+        self.verbose_stderr = verbose_stderr;
+        self
+    }
+
+    /// Like `--timestamps`: prefix each `-v` message with the UTC time it was produced.
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Like `--null`: end each message on stdout with NUL instead of a newline.
+    pub fn with_null(mut self, null: bool) -> Self {
+        self.null = null;
+        self
+    }
+
+    /// Like `-m`: the mode applied to each created directory. Relative modes resolve against
+    /// the umask: the current process umask, or the one given to [`Config::with_umask`].
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode.with_umask(self.umask.unwrap_or_else(current_umask)));
+        self
+    }
+
+    /// Like `--umask`: create directories with `umask` instead of the process umask. The
+    /// process umask is set for the duration of [`run`] and restored afterwards; `-m` still
+    /// decides the leaf's mode.
+    pub fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = Some(umask);
+        self.mode = self.mode.map(|mode| mode.with_umask(umask));
+        self
+    }
+
+    /// Like `--mode-format`: the notation `PATH:MODE` suffixes must use under
+    /// [`Config::with_per_arg_mode`]. Modes passed to [`Config::with_mode`] are already parsed.
+    pub fn with_mode_format(mut self, mode_format: ModeFormat) -> Self {
+        self.mode_format = mode_format;
+        self
+    }
+
+    /// Like `--per-arg-mode`: read a `PATH:MODE` suffix on each directory, which overrides the
+    /// mode for that directory alone. Write `\:` for a colon that is part of the path.
+    pub fn with_per_arg_mode(mut self, per_arg_mode: bool) -> Self {
+        self.per_arg_mode = per_arg_mode;
+        self
+    }
+
+    /// Like `-n`: report what would be created without touching the filesystem.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Like `--diff`: instead of creating anything, print how each directory's mode would
+    /// change, as `'dir': 0700 -> 0755`, or `'dir': (new) -> 0755` for one that does not exist.
+    pub fn with_diff(mut self, diff: bool) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    /// Like `--ignore-existing`: treat an already existing directory as success without
+    /// creating missing parents.
+    pub fn with_ignore_existing(mut self, ignore_existing: bool) -> Self {
+        self.ignore_existing = ignore_existing;
+        self
+    }
+
+    /// Like `--no-clobber-mode`: apply the mode only to directories this run created, leaving
+    /// existing ones found under `-p` or `--ignore-existing` as they are.
+    pub fn with_no_clobber_mode(mut self, no_clobber_mode: bool) -> Self {
+        self.no_clobber_mode = no_clobber_mode;
+        self
+    }
+
+    /// Like `-R`: also apply the mode to every existing directory below the leaf, without
+    /// following symlinks, whenever the leaf itself receives it.
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Like `--strict-parents`: fail instead of creating anything when a parent is missing.
+    pub fn with_strict_parents(mut self, strict_parents: bool) -> Self {
+        self.strict_parents = strict_parents;
+        self
+    }
+
+    /// Like `--backup`: rename a file that is in the way to `NAME~` and create the directory.
+    pub fn with_backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Like `-Z`/`--context`: the SELinux context given to each created directory.
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Like `--owner`: the user and group given to each created directory.
+    pub fn with_owner(mut self, owner: Owner) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// Like `--format`: how results are reported on standard output.
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Like `--total`: print a summary of created directories and errors to stderr.
+    pub fn with_total(mut self, total: bool) -> Self {
+        self.total = total;
+        self
+    }
+
+    /// Like `--report-depth`: print how many directories each argument created.
+    pub fn with_report_depth(mut self, report_depth: bool) -> Self {
+        self.report_depth = report_depth;
+        self
+    }
+
+    /// Like `--report-noop`: say so when an argument needed no new directory because it
+    /// already exists, as `-p` and `--ignore-existing` otherwise succeed silently.
+    pub fn with_report_noop(mut self, report_noop: bool) -> Self {
+        self.report_noop = report_noop;
+        self
+    }
+
+    /// Like `--tree`: print the created directories as a tree at the end, in place of the
+    /// `created directory` lines of `-v`.
+    pub fn with_tree(mut self, tree: bool) -> Self {
+        self.tree = tree;
+        self
+    }
+
+    /// Like `--progress`: show a progress bar on stderr while directories are created. It is
+    /// only drawn when stderr is a terminal.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Like `--check-space`: warn before creating anything if a filesystem has fewer free
+    /// inodes than the directories to be made on it. Only checked on Unix.
+    pub fn with_check_space(mut self, check_space: bool) -> Self {
+        self.check_space = check_space;
+        self
+    }
+
+    /// Like `--max-dirs`: refuse to create anything if the arguments would create more than
+    /// `max_dirs` directories, parents made by `-p` included.
+    pub fn with_max_dirs(mut self, max_dirs: usize) -> Self {
+        self.max_dirs = Some(max_dirs);
+        self
+    }
+
+    /// Like `--jobs`: process independent arguments on up to `jobs` threads. `0` and `1`
+    /// both mean serial. Output, `--format jsonl` records included, is printed in argument
+    /// order once every directory has been processed.
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Like `-q`: don't print per-directory error messages; the exit status still reports
+    /// failures.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Like `--exit-on-error`: stop at the first directory that cannot be created or given its
+    /// mode. `false`, like `--keep-going`, attempts every directory and reports all failures.
+    pub fn with_exit_on_error(mut self, exit_on_error: bool) -> Self {
+        self.exit_on_error = exit_on_error;
+        self
+    }
+
+    /// Like `--transaction`: if any directory fails, remove every directory this run created,
+    /// newest first. Directories that existed beforehand are never removed.
+    pub fn with_transaction(mut self, transaction: bool) -> Self {
+        self.transaction = transaction;
+        self
+    }
+
+    /// Like setting `POSIXLY_CORRECT`: word diagnostics exactly like GNU mkdir.
+    pub fn with_posixly_correct(mut self, posixly_correct: bool) -> Self {
+        self.posixly_correct = posixly_correct;
+        self
+    }
+
+    /// Like `--reference`: copy the mode of an existing path instead of using [`Mode`].
+    pub fn with_reference(mut self, reference: PathBuf) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Like `-C`: create relative directories under `base` instead of the working directory.
+    pub fn with_base(mut self, base: PathBuf) -> Self {
+        self.base = Some(base);
+        self
+    }
+
+    /// Like `--root`: create every directory, absolute or relative, under `root`, which must
+    /// already exist. Relative directories are joined onto `-C` first.
+    pub fn with_root(mut self, root: PathBuf) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// The mode given with `-m` or [`Config::with_mode`], if any.
+    pub fn mode(&self) -> Option<&Mode> {
+        self.mode.as_ref()
+    }
+
+    /// The notation modes are parsed in, as chosen with `--mode-format`.
+    pub fn mode_format(&self) -> ModeFormat {
+        self.mode_format
+    }
+
+    /// The directories to create, as given on the command line.
+    pub fn dirs(&self) -> &[String] {
+        &self.dir_name
+    }
+
+    /// Whether `-p` is set.
+    pub fn parents(&self) -> bool {
+        self.parents
+    }
+
+    /// Whether `-v` is set at any level.
+    pub fn verbose(&self) -> bool {
+        self.verbose > 0
+    }
+
+    /// Makes the directories and sets their modes through `fs` instead of [`RealFs`].
+    pub fn with_fs(mut self, fs: impl Fs + 'static) -> Self {
+        self.fs = FsHandle(Arc::new(fs));
+        self
+    }
+
+    /// Formats a failure to `action` on `path` as `mkdirr: <action> '<path>': <error>`.
+    /// Under `POSIXLY_CORRECT` the ` (os error N)` suffix is dropped to match GNU exactly.
+    fn diagnostic(&self, action: &str, path: &Path, e: &dyn PlainMessage) -> String {
+        let message = if self.posixly_correct {
+            e.plain_message()
+        } else {
+            e.to_string()
+        };
+        format!("mkdirr: {action} {}: {message}", quote(path))
+    }
+}
+
+/// Returns the `mkdirr` command-line definition used by [`read_config`], for generating
+/// completions or parsing an argument list with `try_get_matches_from`.
+pub fn build_cli() -> Command {
+    Command::new("mkdirr")
         .version("0.1.0")
         .author("Edgar Asatryan <easatryan2000@gmail.com>")
         .about("Rust mkdir")
-        .args([
+        .after_help(concat!(
+            "Examples:\n",
+            "  mkdirr -p a/b/c           Create a/b/c and any missing parents\n",
+            "  mkdirr -m 755 foo         Create foo with mode rwxr-xr-x\n",
+            "  mkdirr -m u=rwx,g=rx bar  Create bar open to its group but not to others\n",
+            "  mkdirr ensure -m 750 a/b  Create a/b if needed and make sure it has mode 750\n",
+            "\n",
+            "Exit status:\n",
+            "  0  all directories were created\n",
+            "  1  one or more directories could not be created\n",
+            "  2  invalid arguments",
+        ))
+        .args(options())
+        // `mkdirr help` must keep creating a directory named `help`.
+        .disable_help_subcommand(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("ensure")
+                .about("Create DIRECTORY and any missing parents, and set MODE on it even if it already exists")
+                .args(options().into_iter().filter(|arg| {
+                    !matches!(
+                        arg.get_id().as_str(),
+                        "parents" | "no_clobber_mode" | "strict_parents"
+                    )
+                })),
+        )
+}
+
+/// The options shared by `mkdirr` and `mkdirr ensure`.
+fn options() -> Vec<Arg> {
+    vec![
             arg!(<DIRECTORY> "Directory(ies)")
                 .action(ArgAction::Append)
+                .required(false)
+                .required_unless_present_any([
+                    "stdin",
+                    "null_stdin",
+                    "from_file",
+                    "generate_completions",
+                    "generate_man",
+                ])
                 .id("dir_name"),
             arg!(-p --parents "No error if existing, make parent directories as needed")
                 .id("parents"),
-            arg!(-v --verbose "Print a message for each created directory").id("verbose"),
-            arg!(-m --mode <MODE> "Set file mode (read, write, execute)")
+            arg!(-v --verbose "Print a message for each created directory; repeat to also report skipped ones")
+                .action(ArgAction::Count)
+                .id("verbose"),
+            arg!(--"verbose-stderr" "Like --verbose, but print the messages to stderr")
+                .conflicts_with("quiet")
+                .id("verbose_stderr"),
+            arg!(--timestamps "With --verbose, prefix each message with an ISO 8601 UTC timestamp")
+                .id("timestamps"),
+            arg!(-z --null "End each message on standard output with NUL, not newline")
+                .id("null"),
+            arg!(-m --mode <MODE> "Set file mode (read, write, execute); repeated modes are applied in order. Defaults to $MKDIRR_MODE")
                 .required(false)
+                .action(ArgAction::Append)
                 .value_parser(value_parser!(Mode))
+                .visible_alias("chmod")
                 .id("mode"),
-        ])
-        .get_matches();
+            arg!(--"mode-format" <FORMAT> "Accept MODE only in octal or only in symbolic notation instead of detecting it")
+                .required(false)
+                .value_parser(["auto", "octal", "symbolic"])
+                .default_value("auto")
+                .id("mode_format"),
+            arg!(--umask <MASK> "Create directories with the octal umask MASK instead of the process umask")
+                .required(false)
+                .value_parser(parse_umask)
+                .id("umask"),
+            arg!(--"per-arg-mode" "Read DIRECTORY as PATH:MODE, setting MODE on that directory alone; write \\: for a colon in PATH")
+                .conflicts_with("reference")
+                .id("per_arg_mode"),
+            arg!(-n --"dry-run" "Print what would be created without touching the filesystem")
+                .id("dry_run"),
+            arg!(--diff "Print how each DIRECTORY's mode would change instead of creating anything")
+                .id("diff"),
+            arg!(--reference <RFILE> "Use RFILE's mode instead of specifying MODE values")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with("mode")
+                .id("reference"),
+            arg!(--"ignore-existing" "No error if the directory already exists")
+                .id("ignore_existing"),
+            arg!(--"no-clobber-mode" "Apply MODE only to new directories, not to existing ones")
+                .id("no_clobber_mode"),
+            arg!(-R --recursive "Also apply MODE to every existing directory below DIRECTORY, without following symlinks")
+                .id("recursive"),
+            arg!(--"strict-parents" "Fail, naming the missing parent, if DIRECTORY's parent does not exist")
+                .conflicts_with("parents")
+                .id("strict_parents"),
+            arg!(--backup "Rename a file in the way of DIRECTORY to DIRECTORY~ and create it anyway")
+                .id("backup"),
+            arg!(-Z --context [CTX] "Set the SELinux security context of each created directory to the default type, or to CTX if specified; CTX may instead be KEY=VALUE pairs (user, role, type, range) replacing parts of the default")
+                .value_parser(value_parser!(Context))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("")
+                .id("context"),
+            arg!(--owner <OWNER> "Set the owner and group of each created directory to USER[:GROUP]")
+                .required(false)
+                .value_parser(value_parser!(Owner))
+                .id("owner"),
+            arg!(--stdin "Also read newline-separated directory names from standard input")
+                .id("stdin"),
+            arg!(-'0' --"null-stdin" "Also read NUL-separated directory names from standard input")
+                .conflicts_with("stdin")
+                .id("null_stdin"),
+            arg!(--"from-file" <FILE> "Also read directory names from FILE, one per line; lines starting with '#' are ignored")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .id("from_file"),
+            arg!(--format <FORMAT> "Report results as human-readable messages, as a JSON array or as JSON lines")
+                .required(false)
+                .value_parser(["human", "json", "jsonl"])
+                .default_value("human")
+                .id("format"),
+            arg!(--total "Print a summary of created directories and errors at the end")
+                .id("total"),
+            arg!(--"report-depth" "Print how many directories were created for each argument")
+                .id("report_depth"),
+            arg!(--"report-noop" "Say so when DIRECTORY already exists and nothing was created for it")
+                .id("report_noop"),
+            arg!(--tree "Print the created directories as a tree at the end instead of one line each")
+                .id("tree"),
+            arg!(--progress "Show a progress bar on stderr while directories are created")
+                .id("progress"),
+            arg!(--"check-space" "Warn if a filesystem has fewer free inodes than the directories to create on it")
+                .id("check_space"),
+            arg!(--"max-dirs" <N> "Refuse to create anything if more than N directories would be created")
+                .required(false)
+                .value_parser(value_parser!(usize))
+                .id("max_dirs"),
+            arg!(-j --jobs <N> "Create independent directories on N threads")
+                .required(false)
+                .value_parser(value_parser!(u16).range(1..))
+                .default_value("1")
+                .id("jobs"),
+            arg!(-q --quiet "Do not print an error message for each directory that fails")
+                .conflicts_with("verbose")
+                .id("quiet"),
+            arg!(--"exit-on-error" "Stop at the first directory that cannot be created or given its mode")
+                .overrides_with("keep_going")
+                .id("exit_on_error"),
+            arg!(-k --"keep-going" "Attempt every directory even after one fails (the default)")
+                .overrides_with("exit_on_error")
+                .id("keep_going"),
+            arg!(--transaction "If any directory fails, remove the ones this run created")
+                .id("transaction"),
+            arg!(--glob "Expand *, ? and [...] in DIRECTORY against existing directories, then create the literal components after the last wildcard")
+                .id("glob"),
+            arg!(-C --directory <DIR> "Create relative directories under DIR instead of the current directory")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .id("base"),
+            arg!(--root <DIR> "Create every DIRECTORY under DIR, treating absolute paths as relative to it")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .id("root"),
+            arg!(--"generate-completions" <SHELL> "Print a completion script for SHELL and exit")
+                .required(false)
+                .value_parser(value_parser!(Shell))
+                .hide(true)
+                .id("generate_completions"),
+            arg!(--"generate-man" "Print the man page in roff format and exit")
+                .hide(true)
+                .id("generate_man"),
+    ]
+}
+
+/// Parses the command line. clap exits with [`EXIT_USAGE`] on invalid arguments, including a
+/// bad `-m`; an `Err` from here, such as an unreadable `--from-file`, is a usage error too.
+pub fn read_config() -> MyResult<Config> {
+    let matches = build_cli().get_matches();
+    // `ensure` takes the same options minus the ones it implies or contradicts.
+    let (app, ensure) = match matches.subcommand() {
+        Some(("ensure", sub)) => (sub, true),
+        _ => (&matches, false),
+    };
 
-    let mode = app.get_one::<Mode>("mode").cloned();
+    if let Some(&shell) = app.get_one::<Shell>("generate_completions") {
+        generate(shell, &mut build_cli(), "mkdirr", &mut io::stdout());
+        process::exit(0);
+    }
+
+    if app.get_flag("generate_man") {
+        Man::new(build_cli()).render(&mut io::stdout())?;
+        process::exit(0);
+    }
+
+    let umask = app.get_one::<u32>("umask").copied();
+    let mode_format = match app.get_one::<String>("mode_format").map(String::as_str) {
+        Some("octal") => ModeFormat::Octal,
+        Some("symbolic") => ModeFormat::Symbolic,
+        _ => ModeFormat::Auto,
+    };
+
+    let mode = match app.get_raw("mode") {
+        // clap has already checked each value in auto mode; parse again in the chosen notation.
+        Some(modes) => modes
+            .map(|raw| {
+                let raw = raw.to_string_lossy();
+                Mode::parse_as(&raw, mode_format).unwrap_or_else(|e| {
+                    build_cli()
+                        .error(
+                            clap::error::ErrorKind::InvalidValue,
+                            format!("invalid value '{raw}' for '--mode <MODE>': {e}"),
+                        )
+                        .exit()
+                })
+            })
+            .reduce(|mode, next| mode.then(&next)),
+        // MKDIRR_MODE supplies a default that any `-m` overrides.
+        None => env::var("MKDIRR_MODE")
+            .ok()
+            .map(|value| {
+                Mode::parse_as(&value, mode_format).map_err(|e| {
+                    MkdirrError::InvalidMode(format!(
+                        "invalid value '{value}' for MKDIRR_MODE: {e}"
+                    ))
+                })
+            })
+            .transpose()?,
+    }
+    .map(|mode| mode.with_umask(umask.unwrap_or_else(current_umask)));
+
+    let mut dir_name = app
+        .get_many::<String>("dir_name")
+        .unwrap_or_default()
+        .map(String::from)
+        .collect::<Vec<String>>();
+
+    if app.get_flag("stdin") {
+        dir_name.extend(read_dir_names(io::stdin().lock(), b'\n')?);
+    } else if app.get_flag("null_stdin") {
+        dir_name.extend(read_dir_names(io::stdin().lock(), b'\0')?);
+    }
+
+    if let Some(path) = app.get_one::<PathBuf>("from_file") {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("cannot read {}: {e}", quote(path)))?;
+        dir_name.extend(
+            contents
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    let base = app.get_one::<PathBuf>("base").cloned();
+    let root = app.get_one::<PathBuf>("root").cloned();
+    if app.get_flag("glob") {
+        dir_name = dir_name
+            .iter()
+            .map(|pattern| expand_glob(pattern, base.as_deref(), root.as_deref()))
+            .collect::<MyResult<Vec<_>>>()?
+            .concat();
+    }
 
     Ok(Config {
-        dir_name: app
-            .get_many::<String>("dir_name")
-            .unwrap()
-            .map(String::from)
-            .collect::<Vec<String>>(),
-        parents: app.get_flag("parents"),
-        verbose: app.get_flag("verbose"),
+        dir_name,
+        parents: ensure || app.get_flag("parents"),
+        verbose: app
+            .get_count("verbose")
+            .max(app.get_flag("verbose_stderr").into()),
+        verbose_stderr: app.get_flag("verbose_stderr"),
+        timestamps: app.get_flag("timestamps"),
+        null: app.get_flag("null"),
         mode,
+        umask,
+        mode_format,
+        per_arg_mode: app.get_flag("per_arg_mode"),
+        dry_run: app.get_flag("dry_run"),
+        diff: app.get_flag("diff"),
+        reference: app.get_one::<PathBuf>("reference").cloned(),
+        ignore_existing: app.get_flag("ignore_existing"),
+        no_clobber_mode: !ensure && app.get_flag("no_clobber_mode"),
+        recursive: app.get_flag("recursive"),
+        reconcile: ensure,
+        backup: app.get_flag("backup"),
+        strict_parents: !ensure && app.get_flag("strict_parents"),
+        format: match app.get_one::<String>("format").map(String::as_str) {
+            Some("json") => OutputFormat::Json,
+            Some("jsonl") => OutputFormat::JsonLines,
+            _ => OutputFormat::Human,
+        },
+        total: app.get_flag("total"),
+        report_depth: app.get_flag("report_depth"),
+        tree: app.get_flag("tree"),
+        report_noop: app.get_flag("report_noop"),
+        progress: app.get_flag("progress"),
+        check_space: app.get_flag("check_space"),
+        max_dirs: app.get_one::<usize>("max_dirs").copied(),
+        quiet: app.get_flag("quiet"),
+        exit_on_error: app.get_flag("exit_on_error"),
+        transaction: app.get_flag("transaction"),
+        base,
+        root,
+        posixly_correct: env::var_os("POSIXLY_CORRECT").is_some(),
+        jobs: app.get_one::<u16>("jobs").copied().map_or(1, usize::from),
+        owner: app.get_one::<Owner>("owner").copied(),
+        context: app.get_one::<Context>("context").cloned(),
+        fs: FsHandle::default(),
     })
 }
 
-fn create_directory(dir_name: &str, parents: bool, verbose: bool) -> MyResult<()> {
-    let path = Path::new(dir_name);
-    let mut verbose_info = String::new();
+/// Expands `pattern` for `--glob`. The components up to the last one containing `*`, `?` or
+/// `[` are matched against existing directories, where `base` and `root` put them, and the
+/// literal components after it are appended to each match: `build/*/out` becomes
+/// `build/a/out` and `build/b/out`. A pattern without wildcards is returned unchanged; one
+/// that matches nothing is an error, so a typo cannot create a directory named `*`.
+fn expand_glob(pattern: &str, base: Option<&Path>, root: Option<&Path>) -> MyResult<Vec<String>> {
+    let path = Path::new(pattern);
+    let components: Vec<Component> = path.components().collect();
+    let Some(last) = components
+        .iter()
+        .rposition(|c| c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+    else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    let prefix: PathBuf = components[..=last].iter().collect();
+    let rest: PathBuf = components[last + 1..].iter().collect();
+    // Matches are returned as the user wrote the pattern, relative to `anchor`, since `run`
+    // applies -C and --root to them again.
+    let (anchor, shown) = if path.is_relative() {
+        (
+            base.map(Path::to_path_buf).unwrap_or_default(),
+            PathBuf::new(),
+        )
+    } else {
+        let anchor: PathBuf = components
+            .iter()
+            .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+            .collect();
+        (anchor.clone(), anchor)
+    };
+    let locate = |path: &Path| match root {
+        Some(root) => under_root(root, path),
+        None => path.to_path_buf(),
+    };
+    let prefix = locate(&anchor.join(prefix));
+    let anchor = locate(&anchor);
+
+    let entries = glob::glob(&prefix.to_string_lossy())
+        .map_err(|e| format!("invalid pattern {}: {e}", quote(path)))?;
+    let mut matches = Vec::new();
+    // Entries that cannot be read are skipped, like a shell does.
+    for entry in entries.filter_map(Result::ok) {
+        // A file matched by a trailing wildcard would only fail with "File exists".
+        if !entry.is_dir() {
+            continue;
+        }
+        let entry = entry
+            .strip_prefix(&anchor)
+            .map_or(entry.clone(), |entry| shown.join(entry));
+        let dir = if rest.as_os_str().is_empty() {
+            entry
+        } else {
+            entry.join(&rest)
+        };
+        matches.push(dir.to_string_lossy().into_owned());
+    }
+
+    if matches.is_empty() {
+        return Err(format!("no directories match {}", quote(path)).into());
+    }
+    Ok(matches)
+}
+
+/// Reads directory names separated by `separator`, skipping empty entries.
+fn read_dir_names(mut reader: impl Read, separator: u8) -> MyResult<Vec<String>> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+
+    input
+        .split(|&b| b == separator)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            String::from_utf8(name.to_vec()).map_err(|e| MkdirrError::InvalidUtf8(e.into_bytes()))
+        })
+        .collect()
+}
+
+/// Formats `time` as an ISO 8601 UTC timestamp with second precision, e.g.
+/// `2024-03-09T14:05:00Z`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Howard Hinnant's civil_from_days, shifted so years start in March and the leap day
+    // falls at the end.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// Quotes `path` for messages: wrapped in single quotes, with backslash, quote and control
+/// characters escaped C-style so names containing newlines stay on one unambiguous line, e.g.
+/// `'weird\nname'`. Bytes that are not valid UTF-8 are shown as `\xNN`.
+pub(crate) fn quote(path: &Path) -> String {
+    quote_bytes(path.as_os_str().as_encoded_bytes())
+}
+
+pub(crate) fn quote_bytes(name: &[u8]) -> String {
+    format!("'{}'", escape_bytes(name))
+}
+
+/// Escapes `name` like [`quote`] does, without the surrounding quotes.
+fn escape_bytes(name: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in name.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\'' => escaped.push_str("\\'"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+                c if c.is_control() => escaped.extend(c.escape_unicode()),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    escaped
+}
+
+/// Returns where `--backup` moves `path`: the same name with `~` appended.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push("~");
+    PathBuf::from(backup)
+}
+
+/// Strips trailing slashes so `foo/` is created and reported as `foo`, keeping a lone `/`.
+fn trim_trailing_slashes(path: &Path) -> &Path {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let mut end = bytes.len();
+    while end > 1 && bytes[end - 1] == b'/' {
+        end -= 1;
+    }
+    // SAFETY: the bytes come from an `OsStr` and are cut just before an ASCII '/', which
+    // keeps them a valid encoding.
+    Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(&bytes[..end]) })
+}
+
+/// Creates `dir` and returns the directories that did not exist before, in creation order
+/// (outermost first). Under `parents` an already existing path is not an error and yields an
+/// empty list; `ignore_existing` does the same for an existing directory without creating
+/// parents. With `dry_run` nothing is created and the returned list is what would have been
+/// created.
+///
+/// Nothing is printed; reporting is left to the caller.
+pub fn create_directory<P: AsRef<Path>>(
+    dir: P,
+    parents: bool,
+    ignore_existing: bool,
+    dry_run: bool,
+) -> MyResult<Vec<PathBuf>> {
+    create_directory_with(&RealFs, dir, parents, ignore_existing, dry_run)
+}
+
+/// Like [`create_directory`], but makes the directories through `fs`.
+pub fn create_directory_with<P: AsRef<Path>>(
+    fs: &dyn Fs,
+    dir: P,
+    parents: bool,
+    ignore_existing: bool,
+    dry_run: bool,
+) -> MyResult<Vec<PathBuf>> {
+    create_with_mode(fs, dir.as_ref(), parents, ignore_existing, dry_run, None)
+}
+
+/// Creates the single directory `path`. A `mode` is passed to `mkdir(2)` so the directory
+/// never exists with more permissions than requested; see [`create_with_mode`]. Other
+/// platforms cannot create a directory with a mode; it is applied afterwards.
+fn make_dir(fs: &dyn Fs, path: &Path, mode: Option<u32>) -> io::Result<()> {
+    match mode {
+        Some(mode) if cfg!(unix) => debug!("mkdir {} with mode {mode:04o}", path.display()),
+        _ => debug!("mkdir {}", path.display()),
+    }
+    retry_interrupted(|| fs.create_dir(path, mode))
+}
+
+/// How many times [`retry_interrupted`] repeats a call that a signal interrupted.
+const INTERRUPT_RETRIES: usize = 3;
+
+/// Runs `op`, repeating it up to [`INTERRUPT_RETRIES`] more times while it fails with
+/// `EINTR`. A signal arriving during `mkdir(2)` or `chmod(2)` says nothing about the
+/// directory, so giving up on it would turn a harmless interruption into a failure.
+pub(crate) fn retry_interrupted<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut retries = 0;
+    loop {
+        match op() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted && retries < INTERRUPT_RETRIES => {
+                debug!("interrupted, retrying: {e}");
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The error `mkdir(2)` returns for `kind`, for failures detected before calling it.
+#[cfg(unix)]
+fn mkdir_error(kind: io::ErrorKind) -> MkdirrError {
+    let code = match kind {
+        io::ErrorKind::AlreadyExists => libc::EEXIST,
+        _ => libc::ENOENT,
+    };
+    MkdirrError::from_create(io::Error::from_raw_os_error(code))
+}
+
+#[cfg(not(unix))]
+fn mkdir_error(kind: io::ErrorKind) -> MkdirrError {
+    MkdirrError::from_create(kind.into())
+}
+
+/// Like [`create_directory`], but the leaf is created with `leaf_mode`, the bits `-m` will
+/// set on it afterwards.
+///
+/// Creating with the default mode and then calling chmod leaves a window in which other
+/// processes can see, and open, a directory laxer than requested: `-m 700` under umask 022
+/// briefly exists as 0755. GNU mkdir clears the umask around `mkdir(2)` instead, but the
+/// umask is process-wide and `--jobs` threads creating parents at the same time would pick
+/// it up. Passing the final mode to `mkdir(2)` gives `leaf_mode & !umask`, which is never
+/// laxer than `leaf_mode`; the chmod that follows only adds the bits the umask removed and
+/// the setuid, setgid and sticky bits.
+fn create_with_mode(
+    fs: &dyn Fs,
+    path: &Path,
+    parents: bool,
+    ignore_existing: bool,
+    dry_run: bool,
+    leaf_mode: Option<u32>,
+) -> MyResult<Vec<PathBuf>> {
+    let path = trim_trailing_slashes(path);
+
+    // mkdir(2) would reject "" anyway, but `-p` would otherwise find nothing to create and
+    // report success.
+    if path.as_os_str().is_empty() {
+        return Err(mkdir_error(io::ErrorKind::NotFound));
+    }
 
     if parents {
+        if path.is_dir() {
+            return Ok(Vec::new());
+        }
         if path.exists() {
-            return Ok(());
+            return Err(mkdir_error(io::ErrorKind::AlreadyExists));
+        }
+
+        // Everything below the first existing ancestor is what the loop below makes.
+        let created = missing_ancestors(path);
+
+        // mkdir(2) would fail with a bare ENOTDIR here; name the culprit instead.
+        if let Some(existing) = created.first().and_then(|dir| dir.parent())
+            && !existing.as_os_str().is_empty()
+            && !existing.is_dir()
+        {
+            return Err(MkdirrError::NotADirectory(existing.to_path_buf()));
         }
 
-        if verbose {
-            for ancestor in path.ancestors() {
-                if ancestor.exists() || ancestor.as_os_str() == "" {
-                    continue;
+        if !dry_run {
+            // One level at a time rather than create_dir_all, so a failure names the
+            // component that could not be made instead of the whole path.
+            for (i, dir) in created.iter().enumerate() {
+                let is_leaf = i == created.len() - 1;
+                match make_dir(fs, dir, leaf_mode.filter(|_| is_leaf)) {
+                    Ok(()) => {}
+                    // Another process may have made it since the walk above.
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => {}
+                    Err(e) if is_leaf => return Err(MkdirrError::from_create(e)),
+                    Err(e) => return Err(MkdirrError::Component(dir.clone(), e)),
                 }
+            }
+        }
+        return Ok(created);
+    }
 
-                verbose_info.insert_str(
-                    0,
-                    format!("created directory '{}'\n", ancestor.display()).as_str(),
-                );
+    if ignore_existing && path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    if dry_run {
+        if path.exists() {
+            return Err(mkdir_error(io::ErrorKind::AlreadyExists));
+        }
+        if path
+            .parent()
+            .is_some_and(|p| p.as_os_str() != "" && !p.is_dir())
+        {
+            return Err(mkdir_error(io::ErrorKind::NotFound));
+        }
+    } else if let Err(e) = make_dir(fs, path, leaf_mode) {
+        // The directory may have appeared since the check above.
+        if ignore_existing && e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() {
+            return Ok(Vec::new());
+        }
+        return Err(MkdirrError::from_create(e));
+    }
+
+    Ok(vec![path.to_path_buf()])
+}
+
+/// What happened to one requested directory, as printed by `--format json` and `jsonl`.
+#[derive(Debug, Serialize)]
+struct DirReport {
+    path: String,
+    created: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Every directory made for this argument, including parents created by `-p`.
+    #[serde(skip)]
+    created_dirs: Vec<PathBuf>,
+    /// Messages for this argument, buffered so `--jobs` can print them in argument order.
+    #[serde(skip)]
+    output: Vec<Line>,
+}
+
+#[derive(Debug)]
+enum Line {
+    Stdout(String),
+    Stderr(String),
+}
+
+impl DirReport {
+    /// Buffers a `-v` message on stdout, or on stderr under `--verbose-stderr`.
+    fn push_verbose(&mut self, config: &Config, line: String) {
+        let line = if config.timestamps {
+            format!("{} {line}", timestamp(SystemTime::now()))
+        } else {
+            line
+        };
+        self.output.push(if config.verbose_stderr {
+            Line::Stderr(line)
+        } else {
+            Line::Stdout(line)
+        });
+    }
+
+    /// Prints the buffered messages; `--quiet` drops the diagnostics on stderr and `--null`
+    /// ends those on stdout with NUL. Under `--format jsonl` the report itself follows.
+    fn print(&self, config: &Config) {
+        let end = if config.null { '\0' } else { '\n' };
+        for line in &self.output {
+            match line {
+                Line::Stdout(line) => print!("{line}{end}"),
+                Line::Stderr(line) if !config.quiet => eprintln!("{line}"),
+                Line::Stderr(_) => {}
+            }
+        }
+        if config.format == OutputFormat::JsonLines {
+            println!(
+                "{}",
+                serde_json::to_string(self).expect("a DirReport always serializes")
+            );
+        }
+    }
+}
+
+/// Creates one requested directory and applies the security context and mode to it,
+/// collecting human-readable messages in the returned report. `mode` is the one for this
+/// argument: its `--per-arg-mode` suffix or `config.mode`. `seen` holds the directories
+/// earlier arguments already created, so each is reported only once per run.
+fn process_directory(
+    dir: &str,
+    config: &Config,
+    mode: Option<&Mode>,
+    reference: Option<&Permissions>,
+    seen: &Mutex<HashSet<PathBuf>>,
+) -> DirReport {
+    let mut report = DirReport {
+        path: dir.to_string(),
+        created: false,
+        mode: None,
+        error: None,
+        created_dirs: Vec::new(),
+        output: Vec::new(),
+    };
+
+    let leaf_mode = match (reference, mode) {
+        (Some(perms), _) => Some(mode_bits(perms)),
+        (None, Some(mode)) => Some(mode.to_octal()),
+        (None, None) => None,
+    };
+
+    let target = trim_trailing_slashes(Path::new(dir));
+    let backed_up = config.backup && target.exists() && !target.is_dir();
+    if backed_up {
+        let backup = backup_path(target);
+        if !config.dry_run
+            && let Err(e) = config.fs.0.rename(target, &backup)
+        {
+            report.output.push(Line::Stderr(config.diagnostic(
+                "cannot back up",
+                target,
+                &e,
+            )));
+            report.error = Some(e.to_string());
+            return report;
+        }
+        if config.verbose > 0 && config.format == OutputFormat::Human {
+            let verb = if config.dry_run {
+                "would rename"
+            } else {
+                "renamed"
+            };
+            report.push_verbose(
+                config,
+                format!("{verb} {} -> {}", quote(target), quote(&backup)),
+            );
+        }
+    }
+
+    let missing_parent = if config.strict_parents {
+        let missing = missing_ancestors(target);
+        // Name the directory's own parent, as mkdir does, not the outermost one missing.
+        (missing.len() > 1).then(|| missing[missing.len() - 2].clone())
+    } else {
+        None
+    };
+
+    // -p may fail after making some parents; --transaction needs to know which to remove.
+    let missing = if config.transaction && !config.dry_run {
+        missing_ancestors(target)
+    } else {
+        Vec::new()
+    };
+
+    let result = if backed_up && config.dry_run {
+        // Nothing was moved aside, so the checks in create_with_mode would still see the file.
+        Ok(vec![target.to_path_buf()])
+    } else if let Some(parent) = missing_parent {
+        Err(MkdirrError::MissingParent(parent))
+    } else {
+        create_with_mode(
+            config.fs.0.as_ref(),
+            Path::new(dir),
+            config.parents,
+            config.ignore_existing,
+            config.dry_run,
+            leaf_mode,
+        )
+    };
+
+    match result {
+        Err(e) => {
+            let path = match &e {
+                MkdirrError::Component(component, _) => component,
+                _ => Path::new(dir),
+            };
+            let mut message = config.diagnostic("cannot create directory", path, &e);
+            // New users rarely know about -p; GNU prints no such hint.
+            let nested = target.parent().is_some_and(|p| !p.as_os_str().is_empty());
+            if matches!(e, MkdirrError::ParentMissing(_))
+                && nested
+                && !config.parents
+                && !config.posixly_correct
+            {
+                message.push_str(" (try -p to create parent directories)");
+            }
+            report.output.push(Line::Stderr(message));
+            if config.verbose > 0 && leaf_mode.is_some() {
+                report.output.push(Line::Stderr(format!(
+                    "mkdirr: not setting mode on {} because creation failed",
+                    quote(Path::new(dir))
+                )));
             }
+            let mut seen = seen.lock().unwrap();
+            report.created_dirs = missing
+                .into_iter()
+                .filter(|dir| dir.is_dir() && seen.insert(dir.clone()))
+                .collect();
+            report.error = Some(e.to_string());
         }
+        Ok(mut created) => {
+            // A dry run creates nothing on disk, so `-pn a/b a/c` would otherwise list `a`
+            // for both arguments.
+            let mut seen = seen.lock().unwrap();
+            created.retain(|dir| seen.insert(dir.clone()));
+            drop(seen);
+
+            report.created = !created.is_empty();
+            // Diagnostics are held back so they follow the verbose lines, which need to know
+            // whether the leaf's mode changed.
+            let mut errors = Vec::new();
+            let mut changed = None;
+            let mut changed_below = Vec::new();
+
+            if report.created && !config.dry_run {
+                for dir in &created {
+                    info!("created directory {}", dir.display());
+                }
+
+                if let Some(context) = &config.context {
+                    for dir in &created {
+                        if let Err(e) = context::apply(dir, context) {
+                            errors.push(Line::Stderr(config.diagnostic(
+                                "cannot set security context on",
+                                dir,
+                                &e,
+                            )));
+                            report.error.get_or_insert(e.to_string());
+                        }
+                    }
+                }
+
+                // Before the chmod below: chown may clear the setuid and setgid bits.
+                if let Some(owner) = &config.owner {
+                    for dir in &created {
+                        if let Err(e) = owner::apply(dir, owner) {
+                            errors.push(Line::Stderr(config.diagnostic(
+                                "cannot change ownership of",
+                                dir,
+                                &e,
+                            )));
+                            report.error.get_or_insert(e.to_string());
+                        }
+                    }
+                }
+            }
+
+            if !config.dry_run && (report.created || config.reconcile || !config.no_clobber_mode) {
+                // A relative mode builds on the umask default for a directory made just now and
+                // on the current mode for one that already existed under `-p` or
+                // `--ignore-existing`.
+                let perms = match (reference, mode) {
+                    (Some(perms), _) => Some(Ok(perms.clone())),
+                    #[cfg(unix)]
+                    (None, Some(mode)) if report.created => Some(Ok(mode.permissions())),
+                    (None, Some(mode)) => {
+                        Some(mode.permissions_for(config.fs.0.as_ref(), Path::new(dir)))
+                    }
+                    (None, None) => None,
+                };
+                // An existing directory already holding the mode gets no verbose line.
+                let previous = if report.created {
+                    None
+                } else {
+                    config
+                        .fs
+                        .0
+                        .metadata(Path::new(dir))
+                        .ok()
+                        .map(|m| mode_bits(&m.permissions()))
+                };
+
+                match perms.map(|perms| {
+                    perms.and_then(|p| {
+                        let bits = mode_bits(&p);
+                        retry_interrupted(|| config.fs.0.set_permissions(Path::new(dir), p.clone()))
+                            .map(|_| bits)
+                    })
+                }) {
+                    Some(Ok(bits)) => {
+                        info!("set mode of {dir} to {bits:04o}");
+                        if previous != Some(bits) {
+                            changed = Some(bits);
+                        }
+                        if config.recursive {
+                            changed_below = set_mode_below(
+                                Path::new(dir),
+                                config,
+                                mode,
+                                reference,
+                                &mut errors,
+                                &mut report.error,
+                            );
+                        }
+                        // Linux keeps the bit on a directory but gives it no meaning, so asking
+                        // for it is usually a mistake; --reference only copies what is there.
+                        if reference.is_none() && mode.is_some_and(Mode::requests_setuid) {
+                            errors.push(Line::Stderr(format!(
+                                "mkdirr: setuid bit is ignored for directory {}",
+                                quote(Path::new(dir))
+                            )));
+                        }
+                    }
+                    Some(Err(e)) => {
+                        errors.push(Line::Stderr(config.diagnostic(
+                            "cannot set permissions on",
+                            Path::new(dir),
+                            &e,
+                        )));
+                        report.error.get_or_insert(e.to_string());
+                    }
+                    None => {}
+                }
+            }
+
+            if config.verbose > 0 && config.format == OutputFormat::Human {
+                let message = if config.dry_run {
+                    "would create directory"
+                } else {
+                    "created directory"
+                };
+                for dir in created.iter().filter(|_| !config.tree) {
+                    report.push_verbose(config, format!("{message} {}", quote(dir)));
+                }
+                // Only the leaf receives `-m`, and -R the directories below it; worded like
+                // `chmod -v`.
+                if let Some(bits) = changed {
+                    report.push_verbose(
+                        config,
+                        format!(
+                            "mode of directory {} changed to {bits:04o}",
+                            quote(Path::new(dir))
+                        ),
+                    );
+                }
+                for (path, bits) in &changed_below {
+                    report.push_verbose(
+                        config,
+                        format!("mode of directory {} changed to {bits:04o}", quote(path)),
+                    );
+                }
+                if created.is_empty() && config.verbose > 1 && !config.report_noop {
+                    report.push_verbose(
+                        config,
+                        format!(
+                            "directory {} already exists, skipping",
+                            quote(Path::new(dir))
+                        ),
+                    );
+                }
+            }
+
+            if config.report_noop && created.is_empty() && config.format == OutputFormat::Human {
+                report.push_verbose(
+                    config,
+                    format!(
+                        "mkdirr: {} already exists, nothing to do",
+                        quote(Path::new(dir))
+                    ),
+                );
+            }
 
-        create_dir_all(path)?;
+            if config.report_depth && !created.is_empty() && config.format == OutputFormat::Human {
+                report.output.push(Line::Stdout(format!(
+                    "mkdirr: {} {} {} for {}",
+                    if config.dry_run {
+                        "would create"
+                    } else {
+                        "created"
+                    },
+                    created.len(),
+                    if created.len() == 1 {
+                        "directory"
+                    } else {
+                        "directories"
+                    },
+                    quote(Path::new(dir))
+                )));
+            }
 
-        if verbose && !verbose_info.is_empty() {
-            print!("{}", verbose_info);
+            report.output.extend(errors);
+            report.created_dirs = created;
         }
-        return Ok(());
     }
 
-    create_dir(dir_name)?;
-    if verbose {
-        println!("created directory '{dir_name}'");
+    if config.format != OutputFormat::Human && !config.dry_run {
+        report.mode = config
+            .fs
+            .0
+            .metadata(Path::new(dir))
+            .ok()
+            .map(|m| format!("{:#o}", mode_bits(&m.permissions())));
     }
-    Ok(())
+
+    report
 }
 
-pub fn run(config: &Config) -> MyResult<()> {
-    let mut exit_status = 0;
-    for dir in config.dir_name.iter() {
-        match create_directory(&dir, config.parents, config.verbose) {
+/// Applies the mode to every directory below `dir` for `-R`, parents before their children,
+/// and returns those whose mode changed with their new bits. Entries are examined without
+/// following symlinks, so a link to a directory elsewhere is left alone. Failures are added
+/// to `errors` and `error`, and the walk goes on.
+fn set_mode_below(
+    dir: &Path,
+    config: &Config,
+    mode: Option<&Mode>,
+    reference: Option<&Permissions>,
+    errors: &mut Vec<Line>,
+    error: &mut Option<String>,
+) -> Vec<(PathBuf, u32)> {
+    let mut changed = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if path != dir {
+            let perms = match (reference, mode) {
+                (Some(perms), _) => Ok(perms.clone()),
+                (None, Some(mode)) => mode.permissions_for(config.fs.0.as_ref(), &path),
+                (None, None) => return changed,
+            };
+            let previous = config
+                .fs
+                .0
+                .metadata(&path)
+                .ok()
+                .map(|m| mode_bits(&m.permissions()));
+            match perms.and_then(|p| {
+                let bits = mode_bits(&p);
+                retry_interrupted(|| config.fs.0.set_permissions(&path, p.clone())).map(|_| bits)
+            }) {
+                Ok(bits) if previous != Some(bits) => changed.push((path.clone(), bits)),
+                Ok(_) => {}
+                Err(e) => {
+                    errors.push(Line::Stderr(config.diagnostic(
+                        "cannot set permissions on",
+                        &path,
+                        &e,
+                    )));
+                    error.get_or_insert(e.to_string());
+                }
+            }
+        }
+
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
             Err(e) => {
-                exit_status = 1;
-                eprintln!("cannot create directory `{dir}` {e}");
+                errors.push(Line::Stderr(config.diagnostic(
+                    "cannot read directory",
+                    &path,
+                    &e,
+                )));
+                error.get_or_insert(e.to_string());
+                continue;
             }
-            Ok(_) => {
-                if let Some(mode) = &config.mode {
-                    set_permissions(dir, mode.into())?;
+        };
+        let mut subdirs: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|entry| entry.path())
+            .collect();
+        subdirs.sort();
+        pending.extend(subdirs.into_iter().rev());
+    }
+    changed
+}
+
+/// Returns the outermost directory that creating `dir` would make, or `dir` itself if it
+/// already exists. Arguments sharing this key touch the same new directories and are kept
+/// on one thread by `--jobs`.
+fn creation_root(dir: &str) -> PathBuf {
+    missing_ancestors(Path::new(dir))
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| PathBuf::from(dir))
+}
+
+/// Counts the distinct directories `dirs` would create: every missing ancestor under `-p`,
+/// otherwise each argument that does not exist yet.
+fn count_new_dirs(dirs: &[&str], parents: bool) -> usize {
+    let mut new_dirs = HashSet::new();
+    for dir in dirs {
+        if parents {
+            new_dirs.extend(missing_ancestors(Path::new(dir)));
+        } else if !Path::new(dir).exists() {
+            new_dirs.insert(PathBuf::from(dir));
+        }
+    }
+    new_dirs.len()
+}
+
+/// Returns the directories that creating `path` with `-p` makes, outermost first: `path`
+/// and its ancestors that do not exist yet.
+///
+/// `.` and `..` are resolved lexically so that `a/./b/../c` yields `a`, `a/b` and `a/c`, the
+/// directories create_dir_all would make, rather than `a/./b/..`. A leading `.` is kept.
+fn missing_ancestors(path: &Path) -> Vec<PathBuf> {
+    let mut dir = PathBuf::new();
+    let mut missing = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir
+                if matches!(dir.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                dir.pop();
+            }
+            Component::Normal(_) => {
+                dir.push(component);
+                if !dir.exists() && !missing.contains(&dir) {
+                    missing.push(dir.clone());
+                }
+            }
+            _ => dir.push(component),
+        }
+    }
+    missing
+}
+
+/// Processes the arguments on `config.jobs` threads. Arguments are grouped by
+/// [`creation_root`] so overlapping paths are handled serially, in order, by one thread;
+/// reports come back in argument order. Under `--exit-on-error` the threads stop picking up
+/// arguments once one has failed, and only the arguments that were processed are reported.
+fn process_parallel(
+    dirs: &[&str],
+    config: &Config,
+    modes: &HashMap<&str, Mode>,
+    reference: Option<&Permissions>,
+    seen: &Mutex<HashSet<PathBuf>>,
+    progress: Option<&ProgressBar>,
+) -> Vec<DirReport> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, dir) in dirs.iter().enumerate() {
+        let group = *group_of.entry(creation_root(dir)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+
+    let next_group = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let slots: Mutex<Vec<Option<DirReport>>> = Mutex::new(dirs.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..config.jobs.min(groups.len()) {
+            scope.spawn(|| {
+                while let Some(group) = groups.get(next_group.fetch_add(1, Ordering::Relaxed)) {
+                    for &i in group {
+                        if failed.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let mode = modes.get(dirs[i]).or(config.mode.as_ref());
+                        let report = process_directory(dirs[i], config, mode, reference, seen);
+                        if config.exit_on_error && report.error.is_some() {
+                            failed.store(true, Ordering::Relaxed);
+                        }
+                        slots.lock().unwrap()[i] = Some(report);
+                        progress.inspect(|bar| bar.inc(1));
+                    }
                 }
+            });
+        }
+    });
+
+    slots.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Splits a `--per-arg-mode` argument at its last unescaped colon into the path and the mode,
+/// which is parsed in `format`. `\:` stands for a colon in the path; an argument without a
+/// bare colon has no mode.
+fn split_mode(arg: &str, format: ModeFormat) -> MyResult<(String, Option<Mode>)> {
+    let mut path = String::with_capacity(arg.len());
+    let mut colon = None;
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&':') => {
+                chars.next();
+                path.push(':');
+            }
+            ':' => {
+                colon = Some(path.len());
+                path.push(':');
+            }
+            c => path.push(c),
+        }
+    }
+
+    let Some(colon) = colon else {
+        return Ok((path, None));
+    };
+    let mode = Mode::parse_as(&path[colon + 1..], format)
+        .map_err(|e| {
+            MkdirrError::InvalidMode(format!("invalid mode in {}: {e}", quote(Path::new(arg))))
+        })?
+        .with_umask(current_umask());
+    path.truncate(colon);
+    Ok((path, Some(mode)))
+}
+
+/// Places each directory where `-C` and `--root` put it: relative directories are joined
+/// onto `base`, then every directory is moved under `root`. Both must already exist; with a
+/// `root`, `base` is looked for under it, as the directories will be.
+fn locate_dirs(base: Option<&Path>, root: Option<&Path>, dirs: &[String]) -> MyResult<Vec<String>> {
+    fn require_dir(path: &Path, action: impl Fn(&dyn fmt::Display) -> String) -> MyResult<()> {
+        if !metadata(path).map_err(|e| action(&e))?.is_dir() {
+            return Err(action(&"Not a directory").into());
+        }
+        Ok(())
+    }
+
+    if let Some(root) = root {
+        require_dir(root, |e| format!("cannot use root {}: {e}", quote(root)))?;
+    }
+    if let Some(base) = base {
+        let located = root.map_or_else(|| base.to_path_buf(), |root| under_root(root, base));
+        require_dir(&located, |e| {
+            format!("cannot change to directory {}: {e}", quote(base))
+        })?;
+    }
+
+    dirs.iter()
+        .map(|dir| {
+            let path = base.map_or_else(|| PathBuf::from(dir), |base| base.join(dir));
+            let path = match root {
+                Some(root) => under_root(root, &path),
+                None => path,
+            };
+            path.into_os_string()
+                .into_string()
+                .map_err(|path| MkdirrError::InvalidUtf8(path.as_encoded_bytes().to_vec()))
+        })
+        .collect()
+}
+
+/// Places `path` under `root`, treating an absolute path as relative to it. `.` and `..` are
+/// resolved lexically and `..` cannot climb above `root`, as in a chroot.
+fn under_root(root: &Path, path: &Path) -> PathBuf {
+    let mut inner = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => inner.push(name),
+            Component::ParentDir => {
+                inner.pop();
             }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    if inner.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(inner)
+    }
+}
+
+/// Drops repeated arguments, keeping the first occurrence. Paths are compared by component,
+/// so `a/b`, `a//b` and `a/b/` name the same directory.
+fn unique_dirs(dirs: &[String]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    dirs.iter()
+        .map(String::as_str)
+        .filter(|&dir| seen.insert(Path::new(dir)))
+        .collect()
+}
+
+/// Creates every configured directory, attempting each distinct path once. Like GNU mkdir,
+/// `-m` is applied only to the leaf directory: parents made by `-p` keep the umask default.
+/// A leaf that already exists under `-p` or `--ignore-existing` gets `-m` too, with relative
+/// modes applied to its current mode, unless `--no-clobber-mode` is given; a new leaf
+/// resolves them against the umask default.
+///
+/// A failure on one directory does not stop the others from being attempted; the returned
+/// exit status is [`EXIT_FAILURE`] if any of them failed and [`EXIT_SUCCESS`] otherwise. An
+/// `Err` means nothing was attempted, e.g. an unreadable `--reference`, and should also exit
+/// with [`EXIT_FAILURE`], except for [`MkdirrError::InvalidMode`]: every `--per-arg-mode`
+/// suffix is checked before the first directory is created, and a bad one is a usage error
+/// that exits with [`EXIT_USAGE`].
+pub fn run(config: &Config) -> MyResult<i32> {
+    create_all(&config.dir_name, config)
+}
+
+/// Like [`run`], but creates `dirs` instead of the directories given to [`Config::new`], so
+/// paths can be passed without building a `Vec<String>` first. Every other setting of
+/// `config` applies. Directory names must be valid UTF-8, as on the command line.
+///
+/// ```no_run
+/// use mkdirr::{Config, create_all};
+/// use std::path::PathBuf;
+///
+/// let dirs = [PathBuf::from("logs/app"), PathBuf::from("logs/db")];
+/// let exit_status = create_all(&dirs, &Config::default().with_parents(true))?;
+/// # Ok::<(), mkdirr::MkdirrError>(())
+/// ```
+pub fn create_all<I, P>(dirs: I, config: &Config) -> MyResult<i32>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let dirs = dirs
+        .into_iter()
+        .map(|dir| {
+            let dir = dir.as_ref();
+            dir.to_str().map(String::from).ok_or_else(|| {
+                MkdirrError::InvalidUtf8(dir.as_os_str().as_encoded_bytes().to_vec())
+            })
+        })
+        .collect::<MyResult<Vec<_>>>()?;
+    let reports = run_reports(&dirs, config)?;
+
+    if reports.iter().any(|r| r.error.is_some()) {
+        Ok(EXIT_FAILURE)
+    } else {
+        Ok(EXIT_SUCCESS)
+    }
+}
+
+/// Like [`run`], printing the same output, but returns the directories that were created,
+/// parents made by `-p` included, in creation order. Arguments that failed contribute nothing;
+/// a dry run returns an empty list.
+pub fn run_collecting(config: &Config) -> MyResult<Vec<PathBuf>> {
+    let reports = run_reports(&config.dir_name, config)?;
+
+    if config.dry_run {
+        return Ok(Vec::new());
+    }
+    Ok(reports
+        .into_iter()
+        .flat_map(|report| report.created_dirs)
+        .collect())
+}
+
+/// Draws `created` as a tree for `--tree`, like `tree(1)`. Each directory whose parent was not
+/// created is a root, shown with its full path; the others are listed by name under their
+/// parent, in creation order. Names are escaped as in [`quote`], but not quoted.
+fn render_tree(created: &[&Path]) -> Vec<String> {
+    fn children(node: &Path, created: &[&Path], prefix: &str, lines: &mut Vec<String>) {
+        let kids: Vec<&Path> = created
+            .iter()
+            .copied()
+            .filter(|dir| dir.parent() == Some(node))
+            .collect();
+        for (i, kid) in kids.iter().enumerate() {
+            let last = i == kids.len() - 1;
+            let name = escape_bytes(
+                kid.file_name()
+                    .unwrap_or(kid.as_os_str())
+                    .as_encoded_bytes(),
+            );
+            lines.push(format!(
+                "{prefix}{}{name}",
+                if last { "└── " } else { "├── " }
+            ));
+            let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+            children(kid, created, &prefix, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for root in created
+        .iter()
+        .filter(|dir| !dir.parent().is_some_and(|parent| created.contains(&parent)))
+    {
+        lines.push(escape_bytes(root.as_os_str().as_encoded_bytes()));
+        children(root, created, "", &mut lines);
+    }
+    lines
+}
+
+/// Removes the directories `reports` created, newest first, for `--transaction`. Only empty
+/// directories are removed, so anything another process put inside one keeps it in place;
+/// the directories that could not be removed stay in their report.
+fn roll_back(reports: &mut [DirReport], config: &Config) {
+    for report in reports.iter_mut().rev() {
+        let mut kept = Vec::new();
+        while let Some(dir) = report.created_dirs.pop() {
+            match config.fs.0.remove_dir(&dir) {
+                Ok(()) => {
+                    if config.verbose > 0 && config.format == OutputFormat::Human {
+                        let line = format!("removed directory {}", quote(&dir));
+                        if config.verbose_stderr {
+                            eprintln!("{line}");
+                        } else {
+                            println!("{line}");
+                        }
+                    }
+                }
+                Err(e) => {
+                    if !config.quiet {
+                        eprintln!("{}", config.diagnostic("cannot remove directory", &dir, &e));
+                    }
+                    kept.push(dir);
+                }
+            }
+        }
+        kept.reverse();
+        report.created = !kept.is_empty();
+        report.created_dirs = kept;
+    }
+}
+
+/// Describes the mode change creating `dir` would make for `--diff`, or `None` if it exists
+/// and already has the requested mode. Only the leaf is considered, as only it receives `-m`.
+fn mode_diff(
+    dir: &Path,
+    config: &Config,
+    mode: Option<&Mode>,
+    reference: Option<&Permissions>,
+) -> MyResult<Option<String>> {
+    let fs = config.fs.0.as_ref();
+    let (current, requested) = match fs.metadata(dir) {
+        Ok(meta) => {
+            let current = mode_bits(&meta.permissions());
+            let requested = match (reference, mode) {
+                (Some(perms), _) => mode_bits(perms),
+                (None, Some(mode)) => mode_bits(&mode.permissions_for(fs, dir)?),
+                (None, None) => current,
+            };
+            (Some(current), requested)
+        }
+        Err(_) => {
+            let requested = match (reference, mode) {
+                (Some(perms), _) => mode_bits(perms),
+                (None, Some(mode)) => mode.to_octal(),
+                (None, None) => 0o777 & !current_umask(),
+            };
+            (None, requested)
+        }
+    };
+
+    Ok(match current {
+        Some(current) if current == requested => None,
+        Some(current) => Some(format!("{}: {current:04o} -> {requested:04o}", quote(dir))),
+        None => Some(format!("{}: (new) -> {requested:04o}", quote(dir))),
+    })
+}
+
+/// Processes every argument and prints the per-directory output and any `--format json` or
+/// `--total` summary, returning one report per distinct entry of `dirs`.
+fn run_reports(dirs: &[String], config: &Config) -> MyResult<Vec<DirReport>> {
+    // Held until every directory is made; per-argument modes read it back as they are parsed.
+    let _umask = config.umask.map(UmaskGuard::set);
+    let reference = match &config.reference {
+        Some(path) => {
+            let perms = config
+                .fs
+                .0
+                .metadata(path)
+                .map_err(|e| format!("failed to get attributes of {}: {e}", quote(path)))?
+                .permissions();
+            #[cfg(unix)]
+            let perms = Permissions::from_mode(mode_bits(&perms));
+            Some(perms)
+        }
+        None => None,
+    };
+
+    // All or nothing: every mode is parsed before the first directory is created.
+    let (dir_name, arg_modes) = if config.per_arg_mode {
+        dirs.iter()
+            .map(|arg| split_mode(arg, config.mode_format))
+            .collect::<MyResult<(Vec<_>, Vec<_>)>>()?
+    } else {
+        (dirs.to_vec(), Vec::new())
+    };
+
+    let dir_name = locate_dirs(config.base.as_deref(), config.root.as_deref(), &dir_name)?;
+
+    // Keyed by the final path; like the path itself, the first of several suffixes wins.
+    let mut modes = HashMap::new();
+    for (dir, mode) in dir_name.iter().zip(arg_modes) {
+        if let Some(mode) = mode {
+            modes.entry(dir.as_str()).or_insert(mode);
+        }
+    }
+
+    let dirs = unique_dirs(&dir_name);
+    if config.diff {
+        for dir in &dirs {
+            let mode = modes.get(dir).or(config.mode.as_ref());
+            if let Some(line) = mode_diff(Path::new(dir), config, mode, reference.as_ref())? {
+                println!("{line}");
+            }
+        }
+        return Ok(Vec::new());
+    }
+    if let Some(limit) = config.max_dirs {
+        let count = count_new_dirs(&dirs, config.parents);
+        if count > limit {
+            return Err(MkdirrError::Other(format!(
+                "refusing to create {count} directories (limit {limit})"
+            )));
+        }
+    }
+    if config.check_space && !config.quiet {
+        for warning in space::check(config.fs.0.as_ref(), &dirs, config.parents) {
+            eprintln!("{warning}");
+        }
+    }
+    let seen = Mutex::new(HashSet::new());
+    let progress = (config.progress && io::stderr().is_terminal())
+        .then(|| ProgressBar::new(dirs.len() as u64));
+    let mut reports = if config.jobs > 1 {
+        let reports = process_parallel(
+            &dirs,
+            config,
+            &modes,
+            reference.as_ref(),
+            &seen,
+            progress.as_ref(),
+        );
+        progress.inspect(ProgressBar::finish_and_clear);
+        reports.iter().for_each(|report| report.print(config));
+        reports
+    } else {
+        let mut reports = Vec::new();
+        for dir in &dirs {
+            let mode = modes.get(dir).or(config.mode.as_ref());
+            let report = process_directory(dir, config, mode, reference.as_ref(), &seen);
+            // Suspending the bar keeps the -v lines above it instead of tearing through it.
+            match &progress {
+                Some(bar) => {
+                    bar.suspend(|| report.print(config));
+                    bar.inc(1);
+                }
+                None => report.print(config),
+            }
+            let failed = report.error.is_some();
+            reports.push(report);
+            if failed && config.exit_on_error {
+                break;
+            }
+        }
+        progress.inspect(ProgressBar::finish_and_clear);
+        reports
+    };
+
+    if config.transaction && !config.dry_run && reports.iter().any(|r| r.error.is_some()) {
+        roll_back(&mut reports, config);
+    }
+
+    debug!(
+        "processed {} directories, {} failed",
+        reports.len(),
+        reports.iter().filter(|r| r.error.is_some()).count()
+    );
+
+    if config.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&reports)?);
+    }
+
+    if config.tree && config.format == OutputFormat::Human {
+        let created: Vec<&Path> = reports
+            .iter()
+            .flat_map(|r| &r.created_dirs)
+            .map(PathBuf::as_path)
+            .collect();
+        for line in render_tree(&created) {
+            println!("{line}");
         }
     }
 
-    if exit_status == 1 {
-        process::exit(exit_status);
+    if config.total {
+        let created: usize = reports.iter().map(|r| r.created_dirs.len()).sum();
+        let errors = reports.iter().filter(|r| r.error.is_some()).count();
+        eprintln!(
+            "mkdirr: created {created} {}, {errors} {}",
+            if created == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            if errors == 1 { "error" } else { "errors" },
+        );
     }
 
-    Ok(())
+    Ok(reports)
 }
@@ -1,7 +1,30 @@
-use mkdirr::{read_config, run};
+use mkdirr::{EXIT_FAILURE, EXIT_USAGE, MkdirrError, read_config, run};
+use std::{env, process};
 
 fn main() {
-    if let Err(err) = read_config().and_then(|config| run(&config)) {
-        eprintln!("{}", err);
+    // Diagnostic logging is opt-in so the regular output stays exactly as documented.
+    if env::var_os("RUST_LOG").is_some() {
+        env_logger::init();
+    }
+
+    let config = match read_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("mkdirr: {}", err);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    match run(&config) {
+        Ok(exit_status) => process::exit(exit_status),
+        Err(err) => {
+            eprintln!("mkdirr: {}", err);
+            // A `--per-arg-mode` suffix is only parsed here, but is as much a usage error as a
+            // bad `-m`.
+            process::exit(match err {
+                MkdirrError::InvalidMode(_) => EXIT_USAGE,
+                _ => EXIT_FAILURE,
+            });
+        }
     }
 }
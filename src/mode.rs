@@ -0,0 +1,575 @@
+use std::{
+    fmt,
+    fs::{Permissions, set_permissions},
+    io,
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    error::ModeParseError,
+    filesystem::{Fs, RealFs},
+    retry_interrupted,
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+const USER: u32 = 0o700;
+const GROUP: u32 = 0o070;
+const OTHER: u32 = 0o007;
+const ALL: u32 = USER | GROUP | OTHER;
+
+const READ: u32 = 0o444;
+const WRITE: u32 = 0o222;
+const EXECUTE: u32 = 0o111;
+
+const SETUID: u32 = 0o4000;
+const SETGID: u32 = 0o2000;
+const STICKY: u32 = 0o1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Remove,
+    Set,
+}
+
+/// A single `who op perms` clause of a relative symbolic mode, stored as bit masks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause {
+    op: Op,
+    who: u32,
+    perms: u32,
+    /// The clause has `X`: execute for `who`, but only on a directory or on a file that
+    /// already has an execute bit.
+    exec_if_any: bool,
+}
+
+/// A permission mode as accepted by `-m`: octal (`755`, `0755` or `0o755`), plain (`rwx`),
+/// symbolic (`u=rwx,g+w`) or words (`user:read,write;group:read`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mode {
+    /// Permission bits of an absolute mode, including setuid, setgid and sticky.
+    bits: u32,
+    /// Classes an absolute mode assigns: those named in its `=` clauses, or all of them for
+    /// octal and plain modes. Decides what a later `-m` replaces in [`Mode::then`].
+    who: u32,
+    /// Clauses applied left to right on top of the directory's current mode when the
+    /// mode uses `+` or `-`; empty for absolute modes.
+    ops: Vec<Clause>,
+    /// Process umask used to compute the default base for relative modes.
+    umask: u32,
+}
+
+fn class_bits(class: char) -> Option<u32> {
+    match class {
+        'u' => Some(USER),
+        'g' => Some(GROUP),
+        'o' => Some(OTHER),
+        'a' => Some(ALL),
+        _ => None,
+    }
+}
+
+fn perm_bits(perm: char) -> Option<u32> {
+    match perm {
+        'r' => Some(READ),
+        'w' => Some(WRITE),
+        'x' => Some(EXECUTE),
+        _ => None,
+    }
+}
+
+/// Returns the setuid, setgid and sticky bits that belong to the classes in `who`.
+fn special_bits(who: u32) -> u32 {
+    [(USER, SETUID), (GROUP, SETGID), (OTHER, STICKY)]
+        .iter()
+        .filter(|(class, _)| who & class != 0)
+        .fold(0, |bits, (_, special)| bits | special)
+}
+
+/// Returns the bits `perms` grants the classes in `who`. Like chmod, `s` is setuid for `u`
+/// and setgid for `g`, and `t` is the sticky bit, which belongs to `o`; a letter that would
+/// affect none of the classes, as in `o+s`, is an error reported against `clause`, the
+/// `index`th of the mode.
+fn clause_bits(who: u32, perms: &str, clause: &str, index: usize) -> Result<u32, ModeParseError> {
+    let special = special_bits(who);
+    let mut bits = 0;
+    for perm in perms.chars() {
+        bits |= match perm {
+            's' if special & (SETUID | SETGID) != 0 => special & (SETUID | SETGID),
+            't' if special & STICKY != 0 => STICKY,
+            perm => {
+                who & perm_bits(perm).ok_or_else(|| ModeParseError::InvalidPerm {
+                    clause: clause.to_string(),
+                    perm,
+                    index,
+                })?
+            }
+        };
+    }
+    Ok(bits)
+}
+
+impl Mode {
+    /// Creates an absolute mode from numeric permission bits, the inverse of
+    /// [`Mode::to_octal`]. Bits above `0o7777` are ignored.
+    pub fn from_octal(bits: u32) -> Self {
+        Mode {
+            bits: bits & 0o7777,
+            who: ALL,
+            ..Mode::default()
+        }
+    }
+
+    /// Parses the `index`th clause of a relative mode, which like chmod may carry several
+    /// actions after one set of classes: `u+r-w` is `u+r,u-w`.
+    fn parse_clause(clause: &str, index: usize) -> Result<Vec<Clause>, ModeParseError> {
+        let op = |c| match c {
+            '+' => Some(Op::Add),
+            '-' => Some(Op::Remove),
+            '=' => Some(Op::Set),
+            _ => None,
+        };
+        let pos = clause
+            .find(|c| op(c).is_some())
+            .ok_or_else(|| ModeParseError::BadClause {
+                clause: clause.to_string(),
+                index,
+            })?;
+        let (class, actions) = clause.split_at(pos);
+
+        let mut who = if class.is_empty() { ALL } else { 0 };
+        for class in class.chars() {
+            who |= class_bits(class).ok_or(ModeParseError::InvalidClass(class))?;
+        }
+
+        // Each operator starts an action that runs until the next one.
+        let ops: Vec<(usize, Op)> = actions
+            .char_indices()
+            .filter_map(|(i, c)| op(c).map(|op| (i, op)))
+            .collect();
+        let ends = ops.iter().skip(1).map(|&(i, _)| i).chain([actions.len()]);
+        ops.iter()
+            .zip(ends)
+            .map(|(&(start, op), end)| {
+                let perms = &actions[start + 1..end];
+                let exec_if_any = perms.contains('X');
+                let perms: String = perms.chars().filter(|&c| c != 'X').collect();
+                Ok(Clause {
+                    op,
+                    who,
+                    perms: clause_bits(who, &perms, clause, index)?,
+                    exec_if_any,
+                })
+            })
+            .collect()
+    }
+
+    /// Records the umask so relative modes resolve against `0o777 & !umask`, the mode a
+    /// directory gets when created without `-m`.
+    pub(crate) fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = umask;
+        self
+    }
+
+    /// Combines two `-m` options so `next` is applied on top of `self`: `-m u=rwx -m g=rx`
+    /// acts like `-m u=rwx,g=rx`, while an octal or plain `next` replaces `self` entirely.
+    pub(crate) fn then(self, next: &Mode) -> Mode {
+        if next.ops.is_empty() && next.who == ALL {
+            return next.clone();
+        }
+
+        match (self.ops.is_empty(), next.ops.is_empty()) {
+            (true, true) => Mode {
+                bits: (self.bits & !(next.who | special_bits(next.who))) | next.bits,
+                who: self.who | next.who,
+                ..self
+            },
+            (true, false) => Mode {
+                bits: next.resolve(self.bits),
+                ..self
+            },
+            (false, true) => {
+                let mut mode = self;
+                mode.ops.push(Clause {
+                    op: Op::Set,
+                    who: next.who,
+                    perms: next.bits,
+                    exec_if_any: false,
+                });
+                mode
+            }
+            (false, false) => {
+                let mut mode = self;
+                mode.ops.extend(next.ops.iter().cloned());
+                mode
+            }
+        }
+    }
+
+    /// Returns the permission bits a directory whose mode is currently `base` ends up with,
+    /// without touching the filesystem. Relative modes apply their `+`, `-` and `=` clauses to
+    /// `base` in order; absolute modes ignore it. Pass `0o777 & !umask` for a directory that
+    /// is about to be created.
+    ///
+    /// `mkdirr` only makes directories, so an `X` clause always grants execute, as
+    /// [`Mode::resolve_for`] does for a directory.
+    ///
+    /// ```
+    /// use mkdirr::Mode;
+    ///
+    /// let mode: Mode = "g+w,o-rwx".parse().unwrap();
+    /// assert_eq!(mode.resolve(0o755), 0o770);
+    /// ```
+    pub fn resolve(&self, base: u32) -> u32 {
+        self.resolve_for(base, true)
+    }
+
+    /// Like [`Mode::resolve`], for a directory if `directory` is set and a file otherwise. The
+    /// two differ only in `X`, which like chmod grants execute on a directory, or on a file
+    /// whose `base` already has an execute bit for some class.
+    pub fn resolve_for(&self, base: u32, directory: bool) -> u32 {
+        if self.ops.is_empty() {
+            return self.bits;
+        }
+
+        let exec_if_any = directory || base & EXECUTE != 0;
+        self.ops.iter().fold(base, |bits, clause| {
+            let perms = if clause.exec_if_any && exec_if_any {
+                clause.perms | (clause.who & EXECUTE)
+            } else {
+                clause.perms
+            };
+            match clause.op {
+                Op::Add => bits | perms,
+                Op::Remove => bits & !perms,
+                Op::Set => (bits & !(clause.who | special_bits(clause.who))) | perms,
+            }
+        })
+    }
+
+    /// Whether the mode itself sets the setuid bit, as `4755` or `u+s` do, rather than
+    /// keeping one the directory already has.
+    pub(crate) fn requests_setuid(&self) -> bool {
+        self.resolve(0) & SETUID != 0
+    }
+
+    /// Returns the numeric permission bits, e.g. `0o755`; setuid, setgid and sticky occupy
+    /// the fourth octal digit. Relative modes are resolved against the umask default.
+    pub fn to_octal(&self) -> u32 {
+        self.resolve(ALL & !self.umask)
+    }
+
+    /// Sets the permissions of `path` to this mode, setuid, setgid and sticky bits included.
+    /// Relative modes are applied to the current mode of `path`.
+    pub fn apply_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let perms = self.permissions_for(&RealFs, path)?;
+        retry_interrupted(|| set_permissions(path, perms.clone()))
+    }
+
+    /// Returns the permissions for [`Mode::to_octal`], so relative modes build on the umask
+    /// default.
+    #[cfg(unix)]
+    pub(crate) fn permissions(&self) -> Permissions {
+        Permissions::from_mode(self.to_octal())
+    }
+
+    /// Returns the permissions to give `path`, resolving relative modes against its
+    /// current mode.
+    #[cfg(unix)]
+    pub(crate) fn permissions_for(&self, fs: &dyn Fs, path: &Path) -> io::Result<Permissions> {
+        let current = mode_bits(&fs.metadata(path)?.permissions());
+        Ok(Permissions::from_mode(self.resolve(current)))
+    }
+
+    /// Other platforms only have a read-only attribute, which is set when the mode leaves
+    /// the owner without write permission.
+    #[cfg(not(unix))]
+    pub(crate) fn permissions_for(&self, fs: &dyn Fs, path: &Path) -> io::Result<Permissions> {
+        let mut perms = fs.metadata(path)?.permissions();
+        let bits = self.resolve(mode_bits(&perms));
+        perms.set_readonly(bits & USER & WRITE == 0);
+        Ok(perms)
+    }
+}
+
+/// Which notation [`Mode::parse_as`] accepts, as chosen with `--mode-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModeFormat {
+    /// Octal if the mode is all digits, symbolic otherwise.
+    #[default]
+    Auto,
+    /// Only numeric modes such as `755`.
+    Octal,
+    /// Only `rwx` and `who op perms` modes such as `u=rwx,g+w`.
+    Symbolic,
+}
+
+impl Mode {
+    /// Parses `s` like [`FromStr`], but rejects the notation `format` excludes instead of
+    /// guessing it from the characters used.
+    pub fn parse_as(s: &str, format: ModeFormat) -> Result<Mode, ModeParseError> {
+        let trimmed = s.trim();
+        let octal = octal_digits(trimmed).is_some();
+
+        match format {
+            ModeFormat::Octal if !octal => Err(ModeParseError::ExpectedOctal(trimmed.to_string())),
+            ModeFormat::Symbolic if octal => {
+                Err(ModeParseError::ExpectedSymbolic(trimmed.to_string()))
+            }
+            _ => s.parse(),
+        }
+    }
+}
+
+/// The digits of a numeric mode, without its `0o` or `0O` prefix, or `None` if `s` is not
+/// numeric. Whether the digits are octal is left to the caller.
+fn octal_digits(s: &str) -> Option<&str> {
+    let digits = s
+        .strip_prefix("0o")
+        .or_else(|| s.strip_prefix("0O"))
+        .unwrap_or(s);
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then_some(digits)
+}
+
+/// Rewrites a word mode such as `user:read,write;group:read` as its `=` clauses, `u=rw,g=r`.
+fn words_to_symbolic(s: &str) -> Result<String, ModeParseError> {
+    let clauses = s
+        .split(';')
+        .map(str::trim)
+        .zip(1..)
+        .filter(|(clause, _)| !clause.is_empty())
+        .map(|(clause, index)| {
+            let unknown = || ModeParseError::UnknownClassOrPerm(clause.to_string());
+            let (class, perms) =
+                clause
+                    .split_once(':')
+                    .ok_or_else(|| ModeParseError::BadClause {
+                        clause: clause.to_string(),
+                        index,
+                    })?;
+            let class = match class.trim() {
+                "user" => 'u',
+                "group" => 'g',
+                "other" => 'o',
+                "all" => 'a',
+                _ => return Err(unknown()),
+            };
+            let perms = perms
+                .split(',')
+                .map(str::trim)
+                .filter(|perm| !perm.is_empty())
+                .map(|perm| match perm {
+                    "read" => Ok('r'),
+                    "write" => Ok('w'),
+                    "execute" => Ok('x'),
+                    _ => Err(unknown()),
+                })
+                .collect::<Result<String, _>>()?;
+            Ok(format!("{class}={perms}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(clauses.join(","))
+}
+
+impl FromStr for Mode {
+    type Err = ModeParseError;
+
+    /// Whitespace around the mode and around each comma-separated clause is ignored, as is a
+    /// single trailing comma, so `u=rwx, g=rx,` parses like `u=rwx,g=rx`. Like GNU chmod,
+    /// class and permission letters are case-sensitive: `U=rwx` is an error.
+    ///
+    /// Only `+` and `-` make a mode relative. An `=`-only mode is absolute even with `X`,
+    /// which there always means `x`, since every target is a directory.
+    ///
+    /// An octal mode is at most four digits, optionally after a `0o` or `0O` prefix. A leading
+    /// `0` is just another digit, so `0755` and `0o755` mean `755`. One more `0` may precede a
+    /// four-digit mode that sets special bits, so `01777` and `0o01777` mean `1777`, but
+    /// `00755` is rejected.
+    ///
+    /// A mode containing `:` or `;` is read as words: `;`-separated clauses of a class
+    /// (`user`, `group`, `other` or `all`), a `:` and a comma-separated list of `read`, `write`
+    /// and `execute`. Each clause assigns like `=`, so `user:read,write;group:read` is
+    /// `u=rw,g=r`. `--per-arg-mode` splits on the last `:`, so it cannot take word modes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains([':', ';']) {
+            return words_to_symbolic(s)?.parse();
+        }
+        let s = s.strip_suffix(',').unwrap_or(s).trim_end();
+
+        if s.is_empty() {
+            return Err(ModeParseError::Empty);
+        }
+
+        if s.contains(['+', '-']) {
+            let ops = s
+                .split(",")
+                .zip(1..)
+                .map(|(clause, index)| Mode::parse_clause(clause.trim(), index))
+                .collect::<Result<Vec<_>, _>>()?
+                .concat();
+
+            Ok(Mode {
+                ops,
+                ..Mode::default()
+            })
+        } else if s.contains("=") {
+            let mut bits = 0;
+            let mut assigned = 0;
+
+            for (group_perms, index) in s.split(",").map(str::trim).zip(1..) {
+                let (class, perms) =
+                    group_perms
+                        .split_once("=")
+                        .ok_or_else(|| ModeParseError::BadClause {
+                            clause: group_perms.to_string(),
+                            index,
+                        })?;
+
+                if let Some(perm) = perms.chars().find(|&c| !"rwxXst=".contains(c)) {
+                    return Err(ModeParseError::InvalidPerm {
+                        clause: group_perms.to_string(),
+                        perm,
+                        index,
+                    });
+                }
+
+                // Like chmod, a later `=` replaces what earlier clauses set for the same class,
+                // so `u=r,u=w` and `u=r=w` are `u=w`. No class means `a`, so `=rw` is `a=rw`.
+                let mut who = if class.is_empty() { ALL } else { 0 };
+                for class in class.chars() {
+                    who |= class_bits(class).ok_or_else(|| {
+                        ModeParseError::UnknownClassOrPerm(group_perms.to_string())
+                    })?;
+                }
+                // `mkdirr` only makes directories, on which `X` always means `x`, so `u=rwX`
+                // stays as absolute as `u=rwx`.
+                let perms = perms.replace('X', "x");
+                for perms in perms.split('=') {
+                    bits = (bits & !(who | special_bits(who)))
+                        | clause_bits(who, perms, group_perms, index)?;
+                }
+                assigned |= who;
+            }
+
+            Ok(Mode {
+                bits,
+                who: assigned,
+                ..Mode::default()
+            })
+        } else if let Some(digits) = octal_digits(s) {
+            let digits = digits
+                .strip_prefix('0')
+                .filter(|rest| rest.len() == 4 && !rest.starts_with('0'))
+                .unwrap_or(digits);
+            if digits.len() > 4 || digits.chars().any(|c| !('0'..='7').contains(&c)) {
+                return Err(ModeParseError::InvalidMode(s.to_string()));
+            }
+
+            let bits = u32::from_str_radix(digits, 8)
+                .map_err(|_| ModeParseError::InvalidMode(s.to_string()))?;
+
+            Ok(Mode::from_octal(bits))
+        } else {
+            if s.chars().any(|c| !"rwx".contains(c)) {
+                return Err(ModeParseError::InvalidMode(s.to_string()));
+            }
+
+            let perms = s.chars().filter_map(perm_bits).fold(0, |acc, p| acc | p);
+
+            Ok(Mode::from_octal(ALL & perms))
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    /// Renders the mode in symbolic form, e.g. `u=rwx,g=rx,o=r`. Absolute modes list only the
+    /// classes that have a permission set; relative modes list their clauses in order.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.ops.is_empty() {
+            let clauses: Vec<String> = self
+                .ops
+                .iter()
+                .map(|clause| {
+                    let op = match clause.op {
+                        Op::Add => '+',
+                        Op::Remove => '-',
+                        Op::Set => '=',
+                    };
+                    let mut perms = perms_str(clause.perms);
+                    if clause.exec_if_any {
+                        perms.push('X');
+                    }
+                    if clause.perms & (SETUID | SETGID) != 0 {
+                        perms.push('s');
+                    }
+                    if clause.perms & STICKY != 0 {
+                        perms.push('t');
+                    }
+                    format!("{}{op}{perms}", who_str(clause.who))
+                })
+                .collect();
+            return write!(f, "{}", clauses.join(","));
+        }
+
+        let classes = [
+            ('u', USER, SETUID, 's'),
+            ('g', GROUP, SETGID, 's'),
+            ('o', OTHER, STICKY, 't'),
+        ];
+
+        let clauses: Vec<String> = classes
+            .iter()
+            .filter(|(_, who, special, _)| self.bits & (who | special) != 0)
+            .map(|(class, who, special, special_char)| {
+                let mut perms = perms_str(self.bits & who);
+                if self.bits & special != 0 {
+                    perms.push(*special_char);
+                }
+                format!("{class}={perms}")
+            })
+            .collect();
+
+        if clauses.is_empty() {
+            write!(f, "a=")
+        } else {
+            write!(f, "{}", clauses.join(","))
+        }
+    }
+}
+
+fn who_str(who: u32) -> String {
+    if who == ALL {
+        return "a".to_string();
+    }
+
+    [(USER, 'u'), (GROUP, 'g'), (OTHER, 'o')]
+        .iter()
+        .filter(|(mask, _)| who & mask != 0)
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+fn perms_str(perms: u32) -> String {
+    [(READ, 'r'), (WRITE, 'w'), (EXECUTE, 'x')]
+        .iter()
+        .filter(|(mask, _)| perms & mask != 0)
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+/// Returns the permission bits of `perms`, including setuid, setgid and sticky. Without Unix
+/// modes, only the read-only attribute is known and `0o555` or `0o777` is reported.
+#[cfg(unix)]
+pub(crate) fn mode_bits(perms: &Permissions) -> u32 {
+    perms.mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+pub(crate) fn mode_bits(perms: &Permissions) -> u32 {
+    if perms.readonly() { 0o555 } else { 0o777 }
+}
@@ -0,0 +1,148 @@
+use std::{ffi::CString, io, path::Path, str::FromStr};
+
+#[cfg(unix)]
+use std::{ffi::c_char, ptr};
+
+/// Ownership requested with `--owner USER[:GROUP]`, resolved to numeric ids when parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl Owner {
+    /// The user id to set, or `None` to leave the owner unchanged.
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// The group id to set, or `None` to leave the group unchanged.
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+}
+
+impl FromStr for Owner {
+    type Err = String;
+
+    /// Accepts `USER`, `USER:GROUP` or `:GROUP`, where each part is a name or a numeric id.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, group) = match s.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (s, None),
+        };
+
+        if user.is_empty() && group.is_none_or(str::is_empty) {
+            return Err(format!("invalid owner: '{}'", s));
+        }
+
+        let uid = (!user.is_empty())
+            .then(|| lookup_id(user, user_id).ok_or_else(|| format!("invalid user: '{}'", user)))
+            .transpose()?;
+        let gid = group
+            .filter(|group| !group.is_empty())
+            .map(|group| {
+                lookup_id(group, group_id).ok_or_else(|| format!("invalid group: '{}'", group))
+            })
+            .transpose()?;
+
+        Ok(Owner { uid, gid })
+    }
+}
+
+/// Resolves `name` as a numeric id, falling back to the `lookup` database query.
+fn lookup_id(name: &str, lookup: fn(&CString) -> Option<u32>) -> Option<u32> {
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        return name.parse().ok();
+    }
+
+    lookup(&CString::new(name).ok()?)
+}
+
+/// Calls a reentrant `get*nam_r` function, growing the string buffer while it reports
+/// `ERANGE`. Returns whether an entry was found.
+#[cfg(unix)]
+fn with_buffer(mut call: impl FnMut(&mut [c_char]) -> libc::c_int) -> bool {
+    let mut buf = vec![0; 1024];
+    loop {
+        match call(&mut buf) {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            rc => return rc == 0,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn user_id(name: &CString) -> Option<u32> {
+    // SAFETY: passwd is plain old data; an all-zero value is valid and is only read after
+    // getpwnam_r has filled it in.
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let found = with_buffer(|buf| {
+        // SAFETY: every pointer is valid for the duration of the call and `buf.len()` is the
+        // real size of `buf`.
+        unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        }
+    });
+
+    (found && !result.is_null()).then_some(pwd.pw_uid)
+}
+
+#[cfg(unix)]
+fn group_id(name: &CString) -> Option<u32> {
+    // SAFETY: group is plain old data; an all-zero value is valid and is only read after
+    // getgrnam_r has filled it in.
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let found = with_buffer(|buf| {
+        // SAFETY: every pointer is valid for the duration of the call and `buf.len()` is the
+        // real size of `buf`.
+        unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        }
+    });
+
+    (found && !result.is_null()).then_some(grp.gr_gid)
+}
+
+/// Other platforms have no user database, so only numeric ids are accepted.
+#[cfg(not(unix))]
+fn user_id(_name: &CString) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn group_id(_name: &CString) -> Option<u32> {
+    None
+}
+
+/// Changes the owner and group of `path` to `owner`.
+///
+/// Only available on Unix builds with the `owner` feature; otherwise this returns an
+/// `Unsupported` error, so a directory that would stay owned by the invoking user instead of
+/// the one `--owner` names is reported rather than passed off as handed over.
+#[cfg(all(feature = "owner", unix))]
+pub(crate) fn apply(path: &Path, owner: &Owner) -> io::Result<()> {
+    std::os::unix::fs::chown(path, owner.uid, owner.gid)
+}
+
+#[cfg(not(all(feature = "owner", unix)))]
+pub(crate) fn apply(_path: &Path, _owner: &Owner) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ownership support is not available in this build",
+    ))
+}
@@ -0,0 +1,78 @@
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use crate::{Fs, missing_ancestors, quote};
+
+/// Returns a warning for each filesystem with fewer free inodes, as `fs` reports them, than
+/// the directories `dirs` would create on it. Each missing parent counts under `-p`; without it an argument makes at
+/// most one directory.
+///
+/// This is only a hint: nothing stops another process from using the inodes up afterwards.
+#[cfg(unix)]
+pub(crate) fn check(fs: &dyn Fs, dirs: &[&str], parents: bool) -> Vec<String> {
+    use std::{fs::metadata, os::unix::fs::MetadataExt};
+
+    // (device, a path on it, directories to create there), in argument order.
+    let mut needed: Vec<(u64, PathBuf, u64)> = Vec::new();
+    for dir in dirs {
+        let missing = missing_ancestors(Path::new(dir));
+        let Some(first) = missing.first() else {
+            continue;
+        };
+        let count = if parents { missing.len() as u64 } else { 1 };
+        let existing = match first.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        // A parent that cannot be examined fails the argument later anyway.
+        let Ok(dev) = metadata(&existing).map(|meta| meta.dev()) else {
+            continue;
+        };
+        match needed.iter_mut().find(|(seen, ..)| *seen == dev) {
+            Some((.., total)) => *total += count,
+            None => needed.push((dev, existing, count)),
+        }
+    }
+
+    needed
+        .into_iter()
+        .filter_map(|(_, path, count)| {
+            let free = fs.free_inodes(&path)?;
+            (free < count).then(|| {
+                format!(
+                    "mkdirr: warning: {count} {} to create but only {free} inodes free on the filesystem of {}",
+                    if count == 1 { "directory" } else { "directories" },
+                    quote(&path)
+                )
+            })
+        })
+        .collect()
+}
+
+/// Inodes available to unprivileged users on the filesystem holding `path`, or `None` if it
+/// cannot be queried or, like btrfs, does not have a fixed number of inodes.
+#[cfg(unix)]
+pub(crate) fn free_inodes(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is valid for writes of a statvfs.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs succeeded, so it filled `stat` in.
+    let stat = unsafe { stat.assume_init() };
+
+    // fsfilcnt_t is 32 bits on some platforms.
+    #[allow(clippy::useless_conversion)]
+    let free = u64::from(stat.f_favail);
+    (stat.f_files != 0).then_some(free)
+}
+
+/// Other platforms have no `statvfs`, so the check is skipped.
+#[cfg(not(unix))]
+pub(crate) fn check(_fs: &dyn crate::Fs, _dirs: &[&str], _parents: bool) -> Vec<String> {
+    Vec::new()
+}
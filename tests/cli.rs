@@ -1,3 +1,5 @@
+#![cfg(unix)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{Rng, distr::Alphanumeric, rng};
@@ -121,7 +123,7 @@ fn success_with_verbose_flag() -> Result<(), Box<dyn std::error::Error>> {
         .args([dir.to_str().unwrap(), "-v"])
         .assert()
         .success()
-        .stdout(predicate::str::is_match(&escape(&expected))?);
+        .stdout(predicate::str::is_match(escape(&expected))?);
 
     assert!(dir.is_dir());
     Ok(())
@@ -158,6 +160,95 @@ fn change_mode_if_directory_exists_and_parents_flag_provided()
     Ok(())
 }
 
+#[test]
+fn no_clobber_mode_keeps_existing_directory_mode() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let existing = tmp.path().join(random_name());
+    let fresh = tmp.path().join(random_name());
+    fs::create_dir(&existing)?;
+    fs::set_permissions(&existing, fs::Permissions::from_mode(0o751))?;
+
+    for flag in ["-p", "--ignore-existing"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "--no-clobber-mode", "-m=700"])
+            .arg(&existing)
+            .arg(&fresh)
+            .assert()
+            .success();
+
+        assert_eq!(
+            fs::metadata(&existing)?.permissions().mode() & 0o777,
+            0o751,
+            "{flag}"
+        );
+        assert_eq!(
+            fs::metadata(&fresh)?.permissions().mode() & 0o777,
+            0o700,
+            "{flag}"
+        );
+        fs::remove_dir(&fresh)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn relative_mode_base_depends_on_whether_directory_existed()
+-> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let existing = tmp.path().join("existing");
+    fs::create_dir(&existing)?;
+    fs::set_permissions(&existing, fs::Permissions::from_mode(0o700))?;
+
+    for flag in ["-p", "--ignore-existing"] {
+        let fresh = tmp.path().join(random_name());
+
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg("umask 022 && exec \"$0\" \"$@\"")
+            .arg(assert_cmd::cargo::cargo_bin(PRG))
+            .args([flag, "-m", "g+w"])
+            .arg(&existing)
+            .arg(&fresh)
+            .status()?;
+
+        assert_eq!(
+            fs::metadata(&fresh)?.permissions().mode() & 0o777,
+            0o775,
+            "{flag}"
+        );
+        assert_eq!(
+            fs::metadata(&existing)?.permissions().mode() & 0o777,
+            0o720,
+            "{flag}"
+        );
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn mode_applies_only_to_leaf_with_parents_flag() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let a = tmp.path().join(random_name());
+    let b = a.join(random_name());
+    let c = b.join(random_name());
+    fs::create_dir(&a)?;
+    fs::set_permissions(&a, fs::Permissions::from_mode(0o750))?;
+    let reference = tmp.path().join(random_name());
+    fs::create_dir(&reference)?;
+    let default = fs::metadata(&reference)?.permissions().mode() & 0o777;
+
+    Command::cargo_bin(PRG)?
+        .args([c.to_str().unwrap(), "-p", "-m=700"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::metadata(&a)?.permissions().mode() & 0o777, 0o750);
+    assert_eq!(fs::metadata(&b)?.permissions().mode() & 0o777, default);
+    assert_eq!(fs::metadata(&c)?.permissions().mode() & 0o777, 0o700);
+    Ok(())
+}
+
 #[test]
 fn test_mode_all_rwx() -> Result<(), Box<dyn std::error::Error>> {
     let tmp = TempDir::new()?;
@@ -248,6 +339,30 @@ fn test_mode_only_other_r() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+#[cfg(target_os = "linux")]
+fn chmod_failure_reports_directory_and_continues() -> Result<(), Box<dyn std::error::Error>> {
+    // procfs refuses chmod even for root; `..` climbs from a fresh directory to /proc/1.
+    let tmp = TempDir::new()?;
+    let unchangeable = format!(
+        "{}/{}{}/proc/1",
+        tmp.path().display(),
+        random_name(),
+        "/..".repeat(64)
+    );
+    let other = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .args(["-p", "-m", "700", &unchangeable, other.to_str().unwrap()])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("cannot set permissions on"));
+
+    let mode = fs::metadata(&other)?.permissions().mode() & 0o777;
+    assert_eq!(mode, 0o700);
+    Ok(())
+}
+
 #[test]
 fn fails_with_empty_parameters() -> Result<(), Box<dyn std::error::Error>> {
     Command::cargo_bin(PRG)?
@@ -262,7 +377,7 @@ fn fails_when_directory_already_exists() -> Result<(), Box<dyn std::error::Error
     let tmp = TempDir::new()?;
     let dir = tmp.path().join(random_name());
     let expected = format!(
-        r"cannot create directory `{}` File exists \(os error 17\)\n?",
+        r"mkdirr: cannot create directory '{}': File exists \(os error 17\)\n?",
         escape(dir.to_str().unwrap())
     );
 
@@ -285,7 +400,7 @@ fn fails_when_param_contains_multiple_directories_with_no_parents_flag()
         .join(random_name())
         .join(random_name());
     let expected = format!(
-        r"cannot create directory `{}` No such file or directory \(os error 2\)\n?",
+        r"mkdirr: cannot create directory '{}': No such file or directory \(os error 2\)\n?",
         escape(dir.to_str().unwrap())
     );
 
@@ -323,3 +438,2153 @@ fn fails_when_mode_is_not_valid() -> Result<(), Box<dyn std::error::Error>> {
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
+
+#[test]
+fn test_mode_octal() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, expected) in [("755", 0o755), ("644", 0o644), ("700", 0o700), ("7", 0o007)] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn fails_when_octal_mode_is_not_valid() -> Result<(), Box<dyn std::error::Error>> {
+    for input in ["08", "9"] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+        let expected = format!(
+            r"error: invalid value '{input}' for '--mode <MODE>': Invalid mode: {input}\n?"
+        );
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .failure()
+            .stderr(predicate::str::is_match(&expected)?);
+
+        assert!(!dir.exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mode_octal_special_bits() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, expected) in [("1777", 0o1777), ("2755", 0o2755), ("0755", 0o755)] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o7777;
+        assert_eq!(mode, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mode_relative_operators() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let reference = tmp.path().join(random_name());
+    fs::create_dir(&reference)?;
+    let base = fs::metadata(&reference)?.permissions().mode() & 0o777;
+
+    for (input, expected) in [
+        ("u+x", base | 0o100),
+        ("o-r", base & !0o004),
+        ("u=rwx,g+w,o-r", ((base & !0o700) | 0o700 | 0o020) & !0o004),
+        ("g-rwx,g+r", (base & !0o070) | 0o040),
+    ] {
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mode_all_class() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, expected) in [("a=rx", 0o555), ("a=r,u+w", 0o644), ("a=rwx,a-w", 0o555)] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mode_combined_classes() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, expected) in [
+        ("ug=rw", 0o660),
+        ("go=r", 0o044),
+        ("uu=r", 0o400),
+        ("ug=rwx,o=r", 0o774),
+        ("a=rwx,go-w", 0o755),
+    ] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn repeated_mode_options_accumulate() -> Result<(), Box<dyn std::error::Error>> {
+    for (modes, expected) in [
+        (["u=rwx", "g=rx"], 0o750),
+        (["700", "g+rx"], 0o750),
+        (["u=rwx,g=rwx", "g=rx"], 0o750),
+        (["a=r,u+w", "go-r"], 0o600),
+        (["u=rwx", "750"], 0o750),
+    ] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .arg(&dir)
+            .args(modes.map(|mode| format!("-m={mode}")))
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "modes {modes:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn repeated_mode_options_replace_special_bits() -> Result<(), Box<dyn std::error::Error>> {
+    for (modes, expected) in [
+        (["u=rwxs", "u=rwx"], 0o700),
+        (["u=rwx,g=rxs", "g=rx"], 0o750),
+        (["u=rwxs", "u=rwX"], 0o700),
+    ] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .arg(&dir)
+            .args(modes.map(|mode| format!("-m={mode}")))
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o7777;
+        assert_eq!(mode, expected, "modes {modes:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn conditional_execute_in_set_clause_acts_as_execute() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let mode = |dir: &str| -> std::io::Result<u32> {
+        Ok(fs::metadata(tmp.path().join(dir))?.permissions().mode() & 0o7777)
+    };
+
+    for (dir, input) in [("lower", "u=rwx"), ("upper", "u=rwX")] {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args(["-m", input, dir])
+            .assert()
+            .success();
+    }
+    assert_eq!(mode("upper")?, mode("lower")?);
+    assert_eq!(mode("upper")?, 0o700);
+
+    fs::create_dir(tmp.path().join("existing"))?;
+    fs::set_permissions(
+        tmp.path().join("existing"),
+        fs::Permissions::from_mode(0o755),
+    )?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "u=rwX", "existing"])
+        .assert()
+        .success();
+    assert_eq!(mode("existing")?, 0o700);
+    Ok(())
+}
+
+#[test]
+fn default_mode_respects_umask() -> Result<(), Box<dyn std::error::Error>> {
+    for (umask, expected) in [("022", 0o755), ("077", 0o700), ("002", 0o775)] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("umask {umask} && exec \"$0\" \"$1\""))
+            .arg(assert_cmd::cargo::cargo_bin(PRG))
+            .arg(&dir)
+            .status()?;
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "umask {umask}");
+    }
+    Ok(())
+}
+
+#[test]
+fn explicit_mode_is_exact_under_restrictive_umask() -> Result<(), Box<dyn std::error::Error>> {
+    for (args, leaf, expected) in [
+        (vec!["-m", "777"], "d", 0o777),
+        (vec!["-m", "2775"], "d", 0o2775),
+        (vec!["-p", "-m", "757"], "a/b", 0o757),
+    ] {
+        let tmp = TempDir::new()?;
+
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("umask 077 && exec \"$0\" \"$@\"")
+            .arg(assert_cmd::cargo::cargo_bin(PRG))
+            .args(&args)
+            .arg(tmp.path().join(leaf))
+            .status()?;
+        assert!(status.success());
+
+        let mode = fs::metadata(tmp.path().join(leaf))?.permissions().mode() & 0o7777;
+        assert_eq!(mode, expected, "args {args:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn relative_mode_respects_umask() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    std::process::Command::new("sh")
+        .arg("-c")
+        .arg("umask 027 && exec \"$0\" \"$1\" -m=o+x")
+        .arg(assert_cmd::cargo::cargo_bin(PRG))
+        .arg(&dir)
+        .status()?;
+
+    let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+    assert_eq!(mode, 0o751);
+    Ok(())
+}
+
+#[test]
+fn mixed_clauses_apply_in_order_to_each_target() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let fresh = tmp.path().join(random_name());
+    let existing = tmp.path().join(random_name());
+    fs::create_dir(&existing)?;
+    fs::set_permissions(&existing, fs::Permissions::from_mode(0o005))?;
+
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg("umask 077 && exec \"$0\" -p -m=u=rw,u+x,g+r \"$1\" \"$2\"")
+        .arg(assert_cmd::cargo::cargo_bin(PRG))
+        .arg(&fresh)
+        .arg(&existing)
+        .status()?;
+    assert!(status.success());
+
+    assert_eq!(fs::metadata(&fresh)?.permissions().mode() & 0o777, 0o740);
+    assert_eq!(fs::metadata(&existing)?.permissions().mode() & 0o777, 0o745);
+    Ok(())
+}
+
+#[test]
+fn failure_among_many_still_creates_others() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let existing = tmp.path().join(random_name());
+    let fresh = tmp.path().join(random_name());
+    fs::create_dir(&existing)?;
+
+    Command::cargo_bin(PRG)?
+        .args([&existing, &fresh])
+        .assert()
+        .code(1);
+
+    assert!(fresh.is_dir());
+    Ok(())
+}
+
+#[test]
+fn dry_run_creates_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let parent = tmp.path().join(random_name());
+    let child = parent.join(random_name());
+    let expected = format!(
+        "would create directory '{}'\nwould create directory '{}'\n",
+        parent.display(),
+        child.display()
+    );
+
+    for flag in ["-n", "--dry-run"] {
+        Command::cargo_bin(PRG)?
+            .args([child.to_str().unwrap(), "-pv", "-m=700", flag])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+
+        assert!(!parent.exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn dry_run_without_verbose_is_silent() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .args([dir.to_str().unwrap(), "-n"])
+        .assert()
+        .success()
+        .stdout("");
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn success_with_reference_option() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let reference = tmp.path().join(random_name());
+    let dir = tmp.path().join(random_name());
+    fs::write(&reference, "")?;
+    fs::set_permissions(&reference, fs::Permissions::from_mode(0o751))?;
+
+    Command::cargo_bin(PRG)?
+        .args([
+            dir.to_str().unwrap(),
+            "--reference",
+            reference.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+    assert_eq!(mode, 0o751);
+    Ok(())
+}
+
+#[test]
+fn fails_when_reference_does_not_exist() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let reference = tmp.path().join(random_name());
+    let dir = tmp.path().join(random_name());
+    let expected = format!(
+        r"mkdirr: failed to get attributes of '{}': No such file or directory \(os error 2\)\n?",
+        escape(reference.to_str().unwrap())
+    );
+
+    Command::cargo_bin(PRG)?
+        .args([
+            dir.to_str().unwrap(),
+            "--reference",
+            reference.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(&expected)?);
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn fails_when_reference_and_mode_are_combined() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .args([dir.to_str().unwrap(), "-m=700", "--reference", "/"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn verbose_parents_are_printed_outermost_first() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "a/b/c"])
+        .assert()
+        .success()
+        .stdout("created directory 'a'\ncreated directory 'a/b'\ncreated directory 'a/b/c'\n");
+
+    assert!(tmp.path().join("a/b/c").is_dir());
+    Ok(())
+}
+
+#[test]
+fn verbose_notes_mode_applied_to_leaf() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "-m", "700", "a/b"])
+        .assert()
+        .success()
+        .stdout(
+            "created directory 'a'\ncreated directory 'a/b'\nmode of directory 'a/b' changed to 0700\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "-m", "700", "a/b"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn verbose_reports_mode_change_after_creation() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "-m", "700", "x"])
+        .assert()
+        .success()
+        .stdout("created directory 'x'\nmode of directory 'x' changed to 0700\n");
+
+    // An existing directory only gets the mode line, and only if its mode changes.
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "-m", "750", "x"])
+        .assert()
+        .success()
+        .stdout("mode of directory 'x' changed to 0750\n");
+    Ok(())
+}
+
+#[test]
+fn verbose_parents_skip_existing_ancestors() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "a/b/c"])
+        .assert()
+        .success()
+        .stdout("created directory 'a/b'\ncreated directory 'a/b/c'\n");
+    Ok(())
+}
+
+#[test]
+fn success_with_ignore_existing_when_dir_exists() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+    fs::create_dir(&dir)?;
+
+    Command::cargo_bin(PRG)?
+        .args([dir.to_str().unwrap(), "--ignore-existing", "-v"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn fails_with_ignore_existing_when_path_is_a_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let file = tmp.path().join(random_name());
+    fs::write(&file, "")?;
+
+    Command::cargo_bin(PRG)?
+        .args([file.to_str().unwrap(), "--ignore-existing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("File exists"));
+    Ok(())
+}
+
+#[test]
+fn fails_with_ignore_existing_when_parent_is_missing() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name()).join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .args([dir.to_str().unwrap(), "--ignore-existing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No such file or directory"));
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[cfg(not(feature = "selinux"))]
+#[test]
+fn context_option_fails_without_selinux_support() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+    let expected = format!(
+        "mkdirr: cannot set security context on '{}': SELinux support is not available in this build",
+        dir.display()
+    );
+
+    for flag in ["-Z", "--context=system_u:object_r:tmp_t:s0"] {
+        let _ = fs::remove_dir(&dir);
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), flag])
+            .assert()
+            .code(1)
+            .stderr(predicate::str::contains(expected.as_str()));
+
+        assert!(dir.is_dir());
+    }
+    Ok(())
+}
+
+#[test]
+fn context_rejects_unknown_components() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--context=level=s0", "dir"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "unknown context component: 'level'",
+        ));
+    assert!(!tmp.path().join("dir").exists());
+    Ok(())
+}
+
+#[cfg(not(feature = "owner"))]
+#[test]
+fn owner_option_fails_without_owner_support() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--owner", "0:0"])
+        .assert()
+        .code(1)
+        .stderr(format!(
+            "mkdirr: cannot change ownership of '{}': ownership support is not available in this build\n",
+            dir.display()
+        ));
+
+    assert!(dir.is_dir());
+    Ok(())
+}
+
+#[cfg(feature = "owner")]
+#[test]
+fn owner_option_changes_ownership_of_created_directories() -> Result<(), Box<dyn std::error::Error>>
+{
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp = TempDir::new()?;
+    let root = fs::metadata(tmp.path())?;
+    let owner = format!("{}:{}", root.uid(), root.gid());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "--owner", &owner, "a/b"])
+        .assert()
+        .success();
+
+    for dir in ["a", "a/b"] {
+        let meta = fs::metadata(tmp.path().join(dir))?;
+        assert_eq!((meta.uid(), meta.gid()), (root.uid(), root.gid()), "{dir}");
+    }
+    Ok(())
+}
+
+#[test]
+fn fails_when_owner_is_unknown() -> Result<(), Box<dyn std::error::Error>> {
+    for (owner, message) in [
+        ("no-such-user-mkdirr", "invalid user: 'no-such-user-mkdirr'"),
+        (
+            "0:no-such-group-mkdirr",
+            "invalid group: 'no-such-group-mkdirr'",
+        ),
+        (":", "invalid owner: ':'"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["--owner", owner, "dir"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(message));
+    }
+    Ok(())
+}
+
+#[test]
+fn success_with_stdin_names() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--stdin", "positional"])
+        .write_stdin("a\n\nb c\nd\n")
+        .assert()
+        .success();
+
+    for name in ["positional", "a", "b c", "d"] {
+        assert!(tmp.path().join(name).is_dir(), "{name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn success_with_nul_separated_stdin_names() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-0", "-p"])
+        .write_stdin("a/b\0with\nnewline\0\0")
+        .assert()
+        .success();
+
+    assert!(tmp.path().join("a/b").is_dir());
+    assert!(tmp.path().join("with\nnewline").is_dir());
+    Ok(())
+}
+
+#[test]
+fn success_with_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let list = tmp.path().join("dirs.txt");
+    fs::write(&list, "# scaffold\nsrc/bin  \n\nlogs\t\n")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--from-file", "dirs.txt", "-p"])
+        .assert()
+        .success();
+
+    assert!(tmp.path().join("src/bin").is_dir());
+    assert!(tmp.path().join("logs").is_dir());
+    assert!(!tmp.path().join("# scaffold").exists());
+    Ok(())
+}
+
+#[test]
+fn fails_when_from_file_is_missing() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args([dir.to_str().unwrap(), "--from-file", "missing.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "mkdirr: cannot read 'missing.txt': No such file or directory",
+        ));
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn json_format_reports_each_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    let output = Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "-m=750", "--format", "json", "fresh", "existing"])
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(
+        json[0],
+        serde_json::json!({"path": "fresh", "created": true, "mode": "0o750"})
+    );
+    assert_eq!(json[1]["path"], "existing");
+    assert_eq!(json[1]["created"], false);
+    assert_eq!(json[1]["error"], "File exists (os error 17)");
+    assert_eq!(json.as_array().map(Vec::len), Some(2));
+    Ok(())
+}
+
+#[test]
+fn jsonl_format_prints_one_object_per_line() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    let output = Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-m=750", "--format", "jsonl", "fresh", "existing"])
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+
+    let lines = output
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(serde_json::from_slice)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        serde_json::json!({"path": "fresh", "created": true, "mode": "0o750"})
+    );
+    assert_eq!(lines[1]["path"], "existing");
+    assert_eq!(lines[1]["error"], "File exists (os error 17)");
+    Ok(())
+}
+
+#[test]
+fn human_format_is_the_default() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "--format", "human", "dir"])
+        .assert()
+        .success()
+        .stdout("created directory 'dir'\n");
+    Ok(())
+}
+
+#[test]
+fn total_counts_parents_and_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--total", "-p", "a/b/c", "d"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("mkdirr: created 4 directories, 0 errors\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--total", "e", "existing"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::ends_with(
+            "mkdirr: created 1 directory, 1 error\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn jobs_keep_output_in_argument_order() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let names: Vec<String> = (0..32).map(|i| format!("d{i}")).collect();
+    let mut args = vec!["-pv", "-j", "4", "shared/x", "shared/y"];
+    args.extend(names.iter().map(String::as_str));
+    let mut expected =
+        "created directory 'shared'\ncreated directory 'shared/x'\ncreated directory 'shared/y'\n"
+            .to_string();
+    for name in &names {
+        expected.push_str(&format!("created directory '{name}'\n"));
+    }
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(&args)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    for name in &names {
+        assert!(tmp.path().join(name).is_dir());
+    }
+    Ok(())
+}
+
+#[test]
+fn jobs_report_failures() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--jobs", "3", "a", "existing", "b"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "mkdirr: cannot create directory 'existing': File exists",
+        ));
+
+    assert!(tmp.path().join("a").is_dir());
+    assert!(tmp.path().join("b").is_dir());
+    Ok(())
+}
+
+#[test]
+fn missing_parent_suggests_parents_flag() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .arg("a/b/c")
+        .assert()
+        .code(1)
+        .stderr(
+            "mkdirr: cannot create directory 'a/b/c': No such file or directory (os error 2) \
+             (try -p to create parent directories)\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-q", "a/b/c"])
+        .assert()
+        .code(1)
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn posixly_correct_uses_gnu_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env("POSIXLY_CORRECT", "1")
+        .args(["existing", "missing/child"])
+        .assert()
+        .code(1)
+        .stderr(
+            "mkdirr: cannot create directory 'existing': File exists\n\
+             mkdirr: cannot create directory 'missing/child': No such file or directory\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn quiet_suppresses_errors_but_keeps_exit_status() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("existing"))?;
+
+    for flag in ["-q", "--quiet"] {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args([flag, "existing", "missing/child"])
+            .assert()
+            .code(1)
+            .stderr("");
+    }
+    Ok(())
+}
+
+#[test]
+fn fails_when_quiet_and_verbose_are_combined() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join(random_name());
+
+    Command::cargo_bin(PRG)?
+        .args([dir.to_str().unwrap(), "-q", "-v"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn verbose_output_drops_trailing_slashes() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "foo/"])
+        .assert()
+        .success()
+        .stdout("created directory 'foo'\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "bar/baz//"])
+        .assert()
+        .success()
+        .stdout("created directory 'bar'\ncreated directory 'bar/baz'\n");
+
+    assert!(tmp.path().join("foo").is_dir());
+    assert!(tmp.path().join("bar/baz").is_dir());
+    Ok(())
+}
+
+#[test]
+fn fails_when_path_component_is_not_a_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+    fs::write(tmp.path().join("a/b"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "a/b/c/d"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: cannot create directory 'a/b/c/d': 'a/b' is not a directory\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "a/b"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("File exists"));
+    Ok(())
+}
+
+#[test]
+fn test_mode_tolerates_whitespace_and_trailing_comma() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, expected) in [
+        ("u=rwx, g=rx", 0o750),
+        (" u=rwx , g=rx ", 0o750),
+        ("u=rwx,", 0o700),
+        (" 755 ", 0o755),
+        ("a=rwx, go-w,", 0o755),
+    ] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), "-m", input])
+            .assert()
+            .success();
+
+        let mode = fs::metadata(&dir)?.permissions().mode() & 0o777;
+        assert_eq!(mode, expected, "mode {input:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn fails_when_mode_uses_uppercase_letters() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, message) in [
+        ("U=rwx", "Unknown class or perm: U=rwx"),
+        ("u=RWX", "Invalid permissions in: u=RWX"),
+        ("U+x", "Unknown class: U"),
+        ("RWX", "Invalid mode: RWX"),
+    ] {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join(random_name());
+        let expected = format!("error: invalid value '{input}' for '--mode <MODE>': {message}");
+
+        Command::cargo_bin(PRG)?
+            .args([dir.to_str().unwrap(), &format!("-m={input}")])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+
+        assert!(!dir.exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn generate_completions_without_directory() -> Result<(), Box<dyn std::error::Error>> {
+    for (shell, marker) in [
+        ("bash", "_mkdirr()"),
+        ("zsh", "#compdef mkdirr"),
+        ("fish", "complete -c mkdirr"),
+        ("powershell", "Register-ArgumentCompleter"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["--generate-completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(marker));
+    }
+    Ok(())
+}
+
+#[test]
+fn generate_man_prints_roff() -> Result<(), Box<dyn std::error::Error>> {
+    Command::cargo_bin(PRG)?
+        .arg("--generate-man")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".TH mkdirr 1"))
+        .stdout(predicate::str::contains("\\-\\-parents"))
+        .stdout(predicate::str::contains("Edgar Asatryan"));
+    Ok(())
+}
+
+#[test]
+fn verbose_stderr_keeps_stdout_clean() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "--verbose-stderr", "a/b"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("created directory 'a'\ncreated directory 'a/b'\n");
+
+    assert!(tmp.path().join("a/b").is_dir());
+    Ok(())
+}
+
+#[test]
+fn repeated_arguments_are_created_once() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "a", "b", "a/", "a//", "b"])
+        .assert()
+        .success()
+        .stdout("created directory 'a'\ncreated directory 'b'\n")
+        .stderr("");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "c/d", "c//d", "c/d/"])
+        .assert()
+        .success()
+        .stdout("created directory 'c'\ncreated directory 'c/d'\n");
+    Ok(())
+}
+
+#[test]
+fn directory_option_prefixes_relative_arguments() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("app"))?;
+    let absolute = tmp.path().join("abs");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-C", "app", "logs", "cache"])
+        .arg(&absolute)
+        .assert()
+        .success();
+
+    assert!(tmp.path().join("app/logs").is_dir());
+    assert!(tmp.path().join("app/cache").is_dir());
+    assert!(absolute.is_dir());
+    assert!(!tmp.path().join("app/abs").exists());
+    Ok(())
+}
+
+#[test]
+fn fails_when_directory_option_does_not_exist() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-C", "missing", "logs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(
+            "mkdirr: cannot change to directory 'missing': No such file or directory",
+        ));
+
+    assert!(!tmp.path().join("logs").exists());
+    Ok(())
+}
+
+#[test]
+fn exit_codes_distinguish_usage_from_failures() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .arg("ok")
+        .assert()
+        .code(0);
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .arg("ok")
+        .assert()
+        .code(1);
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-m", "u=rwz", "bad-mode"])
+        .assert()
+        .code(2);
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--from-file", "missing.txt"])
+        .assert()
+        .code(2);
+    Command::cargo_bin(PRG)?.assert().code(2);
+
+    assert!(!tmp.path().join("bad-mode").exists());
+    Ok(())
+}
+
+#[test]
+fn names_with_special_characters_are_escaped() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args([
+            "-v",
+            "weird\nname",
+            "it's",
+            "tab\there",
+            "back\\slash",
+            "plain name",
+        ])
+        .assert()
+        .success()
+        .stdout(concat!(
+            "created directory 'weird\\nname'\n",
+            "created directory 'it\\'s'\n",
+            "created directory 'tab\\there'\n",
+            "created directory 'back\\\\slash'\n",
+            "created directory 'plain name'\n",
+        ));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .arg("weird\nname")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "mkdirr: cannot create directory 'weird\\nname': File exists",
+        ));
+
+    assert!(tmp.path().join("weird\nname").is_dir());
+
+    fs::write(tmp.path().join("it's a file"), "")?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "it's a file/sub"])
+        .assert()
+        .code(1)
+        .stderr(
+            "mkdirr: cannot create directory 'it\\'s a file/sub': 'it\\'s a file' is not a directory\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8_names_are_escaped() -> Result<(), Box<dyn std::error::Error>> {
+    Command::cargo_bin(PRG)?
+        .arg("--stdin")
+        .write_stdin(b"bad\xffname\n".to_vec())
+        .assert()
+        .code(2)
+        .stderr("mkdirr: invalid UTF-8 in directory name 'bad\\xffname'\n");
+    Ok(())
+}
+
+#[test]
+fn report_depth_counts_only_new_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "--report-depth", "a/b/c/d", "e", "a"])
+        .assert()
+        .success()
+        .stdout(concat!(
+            "mkdirr: created 3 directories for 'a/b/c/d'\n",
+            "mkdirr: created 1 directory for 'e'\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn mode_defaults_to_mkdirr_mode_env() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env("MKDIRR_MODE", "u=rwx,g=rx")
+        .arg("from-env")
+        .assert()
+        .success();
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env("MKDIRR_MODE", "u=rwx,g=rx")
+        .args(["-m", "700", "from-flag"])
+        .assert()
+        .success();
+
+    let mode = |name: &str| -> std::io::Result<u32> {
+        Ok(fs::metadata(tmp.path().join(name))?.permissions().mode() & 0o777)
+    };
+    assert_eq!(mode("from-env")?, 0o750);
+    assert_eq!(mode("from-flag")?, 0o700);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env("MKDIRR_MODE", "u=rwz")
+        .arg("bad")
+        .assert()
+        .code(2)
+        .stderr("mkdirr: invalid value 'u=rwz' for MKDIRR_MODE: Invalid permissions in: u=rwz\n");
+    assert!(!tmp.path().join("bad").exists());
+    Ok(())
+}
+
+#[test]
+fn shared_new_parent_is_reported_once() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    for flags in ["-pvn", "-pv"] {
+        let verb = if flags == "-pvn" {
+            "would create"
+        } else {
+            "created"
+        };
+
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args([flags, "a/b", "a/c"])
+            .assert()
+            .success()
+            .stdout(format!(
+                "{verb} directory 'a'\n{verb} directory 'a/b'\n{verb} directory 'a/c'\n"
+            ));
+    }
+    Ok(())
+}
+
+#[test]
+fn parents_failure_names_the_failing_component() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let long = "x".repeat(300);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", &format!("a/{long}/c")])
+        .assert()
+        .code(1)
+        .stderr(format!(
+            "mkdirr: cannot create directory 'a/{long}': File name too long (os error 36)\n"
+        ));
+
+    assert!(tmp.path().join("a").is_dir());
+    Ok(())
+}
+
+#[test]
+fn logging_is_enabled_only_by_rust_log() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env_remove("RUST_LOG")
+        .arg("quiet")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .env("RUST_LOG", "info")
+        .args(["-m", "700", "logged"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains("created directory logged"))
+        .stderr(predicate::str::contains("set mode of logged to 0700"));
+    Ok(())
+}
+
+#[test]
+fn help_shows_examples() -> Result<(), Box<dyn std::error::Error>> {
+    Command::cargo_bin(PRG)?
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Examples:"))
+        .stdout(predicate::str::contains("mkdirr -p a/b/c"))
+        .stdout(predicate::str::contains("mkdirr -m 755 foo"))
+        .stdout(predicate::str::contains("mkdirr -m u=rwx,g=rx bar"));
+    Ok(())
+}
+
+#[test]
+fn fails_on_empty_directory_argument() -> Result<(), Box<dyn std::error::Error>> {
+    for args in [vec![""], vec!["-p", ""], vec!["--ignore-existing", ""]] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .assert()
+            .code(1)
+            .stderr("mkdirr: cannot create directory '': No such file or directory (os error 2)\n");
+    }
+    Ok(())
+}
+
+#[test]
+fn double_verbose_reports_skipped_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "a", "b"])
+        .assert()
+        .success()
+        .stdout("created directory 'b'\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pvv", "a", "c"])
+        .assert()
+        .success()
+        .stdout("directory 'a' already exists, skipping\ncreated directory 'c'\n");
+    Ok(())
+}
+
+#[test]
+fn mode_format_rejects_the_other_notation() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    for (format, mode, message) in [
+        ("octal", "u=rwx", "Expected an octal mode: u=rwx"),
+        ("symbolic", "755", "Expected a symbolic mode: 755"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args(["--mode-format", format, "-m", mode, "dir"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(format!(
+                "invalid value '{mode}' for '--mode <MODE>': {message}"
+            )));
+    }
+    assert!(!tmp.path().join("dir").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--mode-format", "octal", "-m", "750", "dir"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::metadata(tmp.path().join("dir"))?.permissions().mode() & 0o777,
+        0o750
+    );
+    Ok(())
+}
+
+#[test]
+fn backup_moves_a_blocking_file_aside() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::write(tmp.path().join("foo"), "contents")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .arg("foo")
+        .assert()
+        .code(1);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-vn", "--backup", "foo"])
+        .assert()
+        .success()
+        .stdout("would rename 'foo' -> 'foo~'\nwould create directory 'foo'\n");
+    assert!(tmp.path().join("foo").is_file());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "--backup", "foo"])
+        .assert()
+        .success()
+        .stdout("renamed 'foo' -> 'foo~'\ncreated directory 'foo'\n");
+
+    assert!(tmp.path().join("foo").is_dir());
+    assert_eq!(fs::read_to_string(tmp.path().join("foo~"))?, "contents");
+    Ok(())
+}
+
+#[test]
+fn strict_parents_names_the_missing_parent() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--strict-parents", "a/b/c"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: cannot create directory 'a/b/c': parent 'a/b' does not exist\n");
+
+    assert!(!tmp.path().join("a/b").exists());
+
+    // Even when 'a' is missing too, the error names the parent of 'a/b/c'.
+    let empty = TempDir::new()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(empty.path())
+        .args(["--strict-parents", "a/b/c"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: cannot create directory 'a/b/c': parent 'a/b' does not exist\n");
+    assert!(!empty.path().join("a").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--strict-parents", "a/new\tline/c"])
+        .assert()
+        .code(1)
+        .stderr(concat!(
+            "mkdirr: cannot create directory 'a/new\\tline/c': ",
+            "parent 'a/new\\tline' does not exist\n",
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["--strict-parents", "-p", "a/b/c"])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn progress_is_silent_when_stderr_is_not_a_terminal() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--progress", "-v", "a", "b"])
+        .assert()
+        .success()
+        .stdout("created directory 'a'\ncreated directory 'b'\n")
+        .stderr("");
+
+    assert!(tmp.path().join("a").is_dir());
+    assert!(tmp.path().join("b").is_dir());
+    Ok(())
+}
+
+#[test]
+fn ensure_creates_a_fresh_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["ensure", "-m", "750", "a/b/c"])
+        .assert()
+        .success();
+
+    assert!(tmp.path().join("a/b").is_dir());
+    assert_eq!(
+        fs::metadata(tmp.path().join("a/b/c"))?.permissions().mode() & 0o777,
+        0o750
+    );
+    Ok(())
+}
+
+#[test]
+fn ensure_reconciles_an_existing_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("a/b");
+    fs::create_dir_all(&dir)?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+
+    for _ in 0..2 {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args(["ensure", "-m", "755", "a/b"])
+            .assert()
+            .success()
+            .stderr("");
+
+        assert_eq!(fs::metadata(&dir)?.permissions().mode() & 0o777, 0o755);
+    }
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["ensure", "--no-clobber-mode", "a/b"])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn parents_verbose_resolves_dot_components() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "a/./b/../c"])
+        .assert()
+        .success()
+        .stdout("created directory 'a'\ncreated directory 'a/b'\ncreated directory 'a/c'\n");
+
+    // The same directories create_dir_all makes for the unnormalized path.
+    let expected = TempDir::new()?;
+    fs::create_dir_all(expected.path().join("a/./b/../c"))?;
+    for dir in ["a/b", "a/c"] {
+        assert!(tmp.path().join(dir).is_dir());
+        assert!(expected.path().join(dir).is_dir());
+    }
+    Ok(())
+}
+
+#[test]
+fn timestamps_prefix_verbose_lines() -> Result<(), Box<dyn std::error::Error>> {
+    const TIME: &str = r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z";
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "--timestamps", "a/b"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(format!(
+            "^{TIME} created directory 'a'\n{TIME} created directory 'a/b'\n$"
+        ))?);
+    Ok(())
+}
+
+#[test]
+fn exit_on_error_stops_at_the_first_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--exit-on-error", "missing/a", "b"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "mkdirr: cannot create directory 'missing/a'",
+        ));
+    assert!(!tmp.path().join("b").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["missing/a", "b"])
+        .assert()
+        .code(1);
+    assert!(tmp.path().join("b").is_dir());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn keep_going_continues_after_a_chmod_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    // Nobody, root included, may change the mode of a /proc process directory.
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "700", "--keep-going", "a", "/proc/1", "b"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "mkdirr: cannot set permissions on '/proc/1': ",
+        ));
+    for dir in ["a", "b"] {
+        assert_eq!(
+            fs::metadata(tmp.path().join(dir))?.permissions().mode() & 0o777,
+            0o700
+        );
+    }
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "700", "--keep-going", "--exit-on-error"])
+        .args(["/proc/1", "c"])
+        .assert()
+        .code(1);
+    assert!(!tmp.path().join("c").exists());
+    Ok(())
+}
+
+#[test]
+fn per_arg_modes_are_validated_before_creating() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--per-arg-mode", "a:700", "b:750", "c:bogus", "d"])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr("mkdirr: invalid mode in 'c:bogus': Invalid mode: bogus\n");
+    assert_eq!(fs::read_dir(tmp.path())?.count(), 0);
+    Ok(())
+}
+
+#[test]
+fn per_arg_mode_reads_a_mode_suffix() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--per-arg-mode", "-m", "711"])
+        .args(["logs:755", "secrets:700", "plain", r"a\:b:750"])
+        .assert()
+        .success();
+
+    for (dir, mode) in [
+        ("logs", 0o755),
+        ("secrets", 0o700),
+        ("plain", 0o711),
+        ("a:b", 0o750),
+    ] {
+        let perms = fs::metadata(tmp.path().join(dir))?.permissions();
+        assert_eq!(perms.mode() & 0o777, mode, "{dir}");
+    }
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--per-arg-mode", "bad:xyz"])
+        .assert()
+        .code(2)
+        .stderr("mkdirr: invalid mode in 'bad:xyz': Invalid mode: xyz\n");
+    assert!(!tmp.path().join("bad").exists());
+    Ok(())
+}
+
+#[test]
+fn per_arg_modes_follow_mode_format() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args([
+            "--per-arg-mode",
+            "--mode-format",
+            "octal",
+            "a:700",
+            "b:u=rwx",
+        ])
+        .assert()
+        .code(2)
+        .stderr("mkdirr: invalid mode in 'b:u=rwx': Expected an octal mode: u=rwx\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args([
+            "--per-arg-mode",
+            "--mode-format",
+            "symbolic",
+            "c:u=rwx",
+            "d:755",
+        ])
+        .assert()
+        .code(2)
+        .stderr("mkdirr: invalid mode in 'd:755': Expected a symbolic mode: 755\n");
+    assert_eq!(fs::read_dir(tmp.path())?.count(), 0);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--per-arg-mode", "--mode-format", "octal", "a:700"])
+        .assert()
+        .success();
+    let perms = fs::metadata(tmp.path().join("a"))?.permissions();
+    assert_eq!(perms.mode() & 0o777, 0o700);
+    Ok(())
+}
+
+#[test]
+fn setuid_mode_warns_but_is_applied() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-m", "4755", "x"])
+        .assert()
+        .success()
+        .stderr("mkdirr: setuid bit is ignored for directory 'x'\n");
+    assert_eq!(
+        fs::metadata(tmp.path().join("x"))?.permissions().mode() & 0o7777,
+        0o4755
+    );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-q", "-m", "4700", "y"])
+        .assert()
+        .success()
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn setuid_kept_from_existing_directory_does_not_warn() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("x");
+    fs::create_dir(&dir)?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o4755))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "g+w", "x"])
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(fs::metadata(&dir)?.permissions().mode() & 0o7777, 0o4775);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "u+s", "x"])
+        .assert()
+        .success()
+        .stderr("mkdirr: setuid bit is ignored for directory 'x'\n");
+    Ok(())
+}
+
+#[test]
+fn current_parent_and_root_directories_already_exist() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    for dir in [".", "..", "/"] {
+        for flags in [&[][..], &["-n"], &["--strict-parents"]] {
+            Command::cargo_bin(PRG)?
+                .current_dir(tmp.path())
+                .args(flags)
+                .arg(dir)
+                .assert()
+                .code(1)
+                .stderr(format!(
+                    "mkdirr: cannot create directory '{dir}': File exists (os error 17)\n"
+                ));
+        }
+
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args(["-pv", dir])
+            .assert()
+            .success()
+            .stdout("");
+    }
+    Ok(())
+}
+
+#[test]
+fn symbolic_sticky_bit() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("shared");
+
+    Command::cargo_bin(PRG)?
+        .args(["-m", "+t"])
+        .arg(&dir)
+        .assert()
+        .success();
+
+    assert_ne!(fs::metadata(&dir)?.permissions().mode() & 0o1000, 0);
+    Ok(())
+}
+
+#[test]
+fn verbose_notes_mode_not_set_when_creation_fails() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("x"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-v", "-m", "700", "x"])
+        .assert()
+        .code(1)
+        .stderr(concat!(
+            "mkdirr: cannot create directory 'x': File exists (os error 17)\n",
+            "mkdirr: not setting mode on 'x' because creation failed\n",
+        ));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-m", "700", "x"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: cannot create directory 'x': File exists (os error 17)\n");
+    Ok(())
+}
+
+#[test]
+fn null_ends_verbose_messages_with_nul() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "--null", "a/b"])
+        .assert()
+        .success()
+        .stdout("created directory 'a'\0created directory 'a/b'\0");
+    Ok(())
+}
+
+#[test]
+fn check_space_is_quiet_when_inodes_suffice() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--check-space", "-p", "a/b/c", "d"])
+        .assert()
+        .success()
+        .stderr("");
+
+    assert!(tmp.path().join("a/b/c").is_dir());
+    assert!(tmp.path().join("d").is_dir());
+    Ok(())
+}
+
+#[test]
+fn transaction_rolls_back_on_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("keep"))?;
+    fs::write(tmp.path().join("file"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--transaction", "-pv", "keep/a/b", "file/c"])
+        .assert()
+        .code(1)
+        .stdout(concat!(
+            "created directory 'keep/a'\n",
+            "created directory 'keep/a/b'\n",
+            "removed directory 'keep/a/b'\n",
+            "removed directory 'keep/a'\n",
+        ))
+        .stderr("mkdirr: cannot create directory 'file/c': 'file' is not a directory\n");
+
+    assert!(tmp.path().join("keep").is_dir());
+    assert!(!tmp.path().join("keep/a").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--transaction", "-p", "keep/a/b", "d"])
+        .assert()
+        .success();
+    assert!(tmp.path().join("keep/a/b").is_dir());
+    Ok(())
+}
+
+#[test]
+fn chmod_is_an_alias_of_mode() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+
+    for (name, args) in [
+        ("a", ["-m", "700"]),
+        ("b", ["--mode", "700"]),
+        ("c", ["--chmod", "700"]),
+        ("d", ["--chmod=u=rwx", "-m=go="]),
+    ] {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args(args)
+            .arg(name)
+            .assert()
+            .success();
+
+        let mode = fs::metadata(tmp.path().join(name))?.permissions().mode() & 0o777;
+        assert_eq!(mode, 0o700, "{args:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn max_dirs_refuses_before_creating_anything() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("a"))?;
+
+    // `a` exists and `a/b` is shared, so this makes a/b, a/b/c and a/b/d.
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--max-dirs", "2", "-p", "a/b/c", "a/b/d"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: refusing to create 3 directories (limit 2)\n");
+    assert!(!tmp.path().join("a/b").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--max-dirs", "3", "-p", "a/b/c", "a/b/d"])
+        .assert()
+        .success();
+    assert!(tmp.path().join("a/b/d").is_dir());
+    Ok(())
+}
+
+#[test]
+fn glob_expands_existing_parents() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir_all(tmp.path().join("build/a"))?;
+    fs::create_dir_all(tmp.path().join("build/b"))?;
+    fs::write(tmp.path().join("build/file"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--glob", "-v", "build/*/out", "plain"])
+        .assert()
+        .success()
+        .stdout(
+            "created directory 'build/a/out'\ncreated directory 'build/b/out'\ncreated directory 'plain'\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--glob", "-p", "-m", "700", "build/*"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+    assert!(tmp.path().join("build/file").is_file());
+    let perms = fs::metadata(tmp.path().join("build/a"))?.permissions();
+    assert_eq!(perms.mode() & 0o777, 0o700);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--glob", "-p", "build/f*"])
+        .assert()
+        .code(2)
+        .stderr("mkdirr: no directories match 'build/f*'\n");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--glob", "missing/*/out"])
+        .assert()
+        .code(2)
+        .stderr("mkdirr: no directories match 'missing/*/out'\n");
+    assert!(!tmp.path().join("missing").exists());
+    Ok(())
+}
+
+#[test]
+fn recursive_applies_mode_below_existing_leaf() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir_all(tmp.path().join("t/a/b"))?;
+    fs::create_dir(tmp.path().join("t/c"))?;
+    fs::create_dir(tmp.path().join("outside"))?;
+    fs::set_permissions(
+        tmp.path().join("outside"),
+        fs::Permissions::from_mode(0o755),
+    )?;
+    std::os::unix::fs::symlink("../outside", tmp.path().join("t/link"))?;
+    let mode = |dir: &str| -> std::io::Result<u32> {
+        Ok(fs::metadata(tmp.path().join(dir))?.permissions().mode() & 0o7777)
+    };
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-p", "-m", "750", "t"])
+        .assert()
+        .success();
+    assert_eq!(mode("t")?, 0o750);
+    assert_ne!(mode("t/a")?, 0o750);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pvR", "-m", "700", "t"])
+        .assert()
+        .success()
+        .stdout(
+            "mode of directory 't' changed to 0700\n\
+             mode of directory 't/a' changed to 0700\n\
+             mode of directory 't/a/b' changed to 0700\n\
+             mode of directory 't/c' changed to 0700\n",
+        );
+    for dir in ["t", "t/a", "t/a/b", "t/c"] {
+        assert_eq!(mode(dir)?, 0o700, "{dir}");
+    }
+    assert_eq!(mode("outside")?, 0o755);
+    Ok(())
+}
+
+#[test]
+fn diff_prints_mode_changes_without_applying_them() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    for (dir, mode) in [("open", 0o700), ("same", 0o755)] {
+        fs::create_dir(tmp.path().join(dir))?;
+        fs::set_permissions(tmp.path().join(dir), fs::Permissions::from_mode(mode))?;
+    }
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--diff", "-m", "755", "open", "same", "new", "new\tline"])
+        .assert()
+        .success()
+        .stdout(concat!(
+            "'open': 0700 -> 0755\n",
+            "'new': (new) -> 0755\n",
+            "'new\\tline': (new) -> 0755\n",
+        ));
+
+    let perms = fs::metadata(tmp.path().join("open"))?.permissions();
+    assert_eq!(perms.mode() & 0o777, 0o700);
+    assert!(!tmp.path().join("new").exists());
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--diff", "-m", "g+w", "open"])
+        .assert()
+        .success()
+        .stdout("'open': 0700 -> 0720\n");
+    Ok(())
+}
+
+#[test]
+fn root_prefixes_absolute_and_relative_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir_all(tmp.path().join("img/usr/lib"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--root", "img", "-v", "/etc", "var", "/../../opt"])
+        .assert()
+        .success()
+        .stdout(
+            "created directory 'img/etc'\ncreated directory 'img/var'\ncreated directory 'img/opt'\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--root", "img", "--glob", "-v", "/usr/*/cache"])
+        .assert()
+        .success()
+        .stdout("created directory 'img/usr/lib/cache'\n");
+
+    // The -C directory only has to exist inside the root, not on the host.
+    let base = format!("/{}-{}", random_name(), random_name());
+    fs::create_dir_all(tmp.path().join("img").join(&base[1..]))?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--root", "img", "-C", &base, "-v", "logs"])
+        .assert()
+        .success()
+        .stdout(format!("created directory 'img{base}/logs'\n"));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--root", "img", "-C", "usr/missing", "logs"])
+        .assert()
+        .code(1)
+        .stderr(
+            "mkdirr: cannot change to directory 'usr/missing': No such file or directory (os error 2)\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--root", "missing", "/etc"])
+        .assert()
+        .code(1)
+        .stderr("mkdirr: cannot use root 'missing': No such file or directory (os error 2)\n");
+    assert!(!tmp.path().join("etc").exists());
+    Ok(())
+}
+
+#[test]
+fn umask_overrides_process_umask() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let mode = |dir: &str| -> std::io::Result<u32> {
+        Ok(fs::metadata(tmp.path().join(dir))?.permissions().mode() & 0o777)
+    };
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--umask", "077", "plain"])
+        .assert()
+        .success();
+    assert_eq!(mode("plain")?, 0o700);
+
+    // -m still decides the leaf; the parent -p makes gets the umask default.
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--umask", "027", "-p", "-m", "755", "a/b"])
+        .assert()
+        .success();
+    assert_eq!(mode("a")?, 0o750);
+    assert_eq!(mode("a/b")?, 0o755);
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["--umask", "8", "c"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid umask '8'"));
+    Ok(())
+}
+
+#[test]
+fn tree_draws_created_directories() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir(tmp.path().join("e"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args([
+            "-pv",
+            "--tree",
+            "a/b/c",
+            "a/b/d/x",
+            "a/y",
+            "e/f",
+            "e",
+            "n\nl/t\tb",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "a\n\
+             ├── b\n\
+             │   ├── c\n\
+             │   └── d\n\
+             │       └── x\n\
+             └── y\n\
+             e/f\n\
+             n\\nl\n\
+             └── t\\tb\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn report_noop_names_existing_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    fs::create_dir_all(tmp.path().join("a/b/c"))?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "a/b/c"])
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-pv", "--report-noop", "a/b/c", "a/d"])
+        .assert()
+        .success()
+        .stdout("mkdirr: 'a/b/c' already exists, nothing to do\ncreated directory 'a/d'\n");
+    Ok(())
+}
@@ -0,0 +1,434 @@
+use mkdirr::{
+    Config, Context, ContextBackend, Fs, MkdirrError, Mode, ModeFormat, RealFs, build_cli,
+    create_all, create_directory, create_directory_with, run, run_collecting,
+};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::{env, process};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tempfile::TempDir;
+
+#[cfg(unix)]
+#[test]
+fn run_with_built_config() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("a").join("b");
+    let config = Config::new(vec![dir.to_str().unwrap().to_string()])
+        .with_parents(true)
+        .with_mode(Mode::from_str("750")?);
+
+    assert_eq!(run(&config)?, 0);
+
+    assert!(dir.is_dir());
+    assert_eq!(fs::metadata(&dir)?.permissions().mode() & 0o777, 0o750);
+    Ok(())
+}
+
+#[cfg(windows)]
+#[test]
+fn run_maps_mode_to_read_only_attribute() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    for (name, mode, readonly) in [("ro", "555", true), ("rw", "755", false)] {
+        let dir = tmp.path().join(name);
+        let config =
+            Config::new(vec![dir.to_str().unwrap().to_string()]).with_mode(Mode::from_str(mode)?);
+
+        assert_eq!(run(&config)?, 0);
+
+        assert_eq!(
+            fs::metadata(&dir)?.permissions().readonly(),
+            readonly,
+            "mode {mode}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn run_reports_failures_through_exit_status() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("a").join("b");
+    let config = Config::new(vec![dir.to_str().unwrap().to_string()]);
+
+    assert_eq!(run(&config)?, 1);
+
+    assert!(!dir.exists());
+    Ok(())
+}
+
+#[test]
+fn create_directory_returns_created_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let a = tmp.path().join("a");
+    let b = a.join("b");
+    let c = b.join("c");
+    fs::create_dir(&a)?;
+
+    assert_eq!(
+        create_directory(&c, true, false, false)?,
+        vec![b.clone(), c.clone()]
+    );
+    assert!(c.is_dir());
+    assert_eq!(
+        create_directory(&c, true, false, false)?,
+        Vec::<PathBuf>::new()
+    );
+
+    let d = a.join("d");
+    assert_eq!(create_directory(&d, false, false, false)?, vec![d.clone()]);
+    assert!(create_directory(&d, false, false, false).is_err());
+    Ok(())
+}
+
+#[test]
+fn create_directory_errors_can_be_matched() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let existing = tmp.path().join("existing");
+    fs::create_dir(&existing)?;
+
+    assert!(matches!(
+        create_directory(&existing, false, false, false),
+        Err(MkdirrError::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        create_directory(tmp.path().join("a/b"), false, false, false),
+        Err(MkdirrError::ParentMissing(_))
+    ));
+    assert!(matches!(
+        create_directory(tmp.path().join("a/b"), false, false, true),
+        Err(MkdirrError::ParentMissing(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn build_cli_parses_argument_lists() {
+    let matches = build_cli()
+        .try_get_matches_from(["mkdirr", "-p", "-m", "750", "a/b"])
+        .unwrap();
+    assert!(matches.get_flag("parents"));
+    assert_eq!(matches.get_one::<Mode>("mode").unwrap().to_octal(), 0o750);
+
+    assert!(build_cli().try_get_matches_from(["mkdirr"]).is_err());
+    assert!(
+        build_cli()
+            .try_get_matches_from(["mkdirr", "-v", "-q", "a"])
+            .is_err()
+    );
+}
+
+#[test]
+fn run_collecting_returns_created_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let a = tmp.path().join("a");
+    fs::create_dir(tmp.path().join("existing"))?;
+    let dirs = ["a/b", "a/c", "existing", "missing/x"]
+        .map(|dir| tmp.path().join(dir).to_str().unwrap().to_string());
+
+    let created = run_collecting(&Config::new(dirs.to_vec()).with_parents(true))?;
+    assert_eq!(
+        created,
+        vec![
+            a.clone(),
+            a.join("b"),
+            a.join("c"),
+            tmp.path().join("missing"),
+            tmp.path().join("missing/x")
+        ]
+    );
+
+    let created = run_collecting(&Config::new(vec![dirs[1].clone(), dirs[0].clone()]))?;
+    assert!(created.is_empty());
+    Ok(())
+}
+
+#[test]
+fn create_all_takes_paths_instead_of_config_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let ignored = tmp.path().join("ignored");
+    let dirs = [tmp.path().join("a/b"), tmp.path().join("c")];
+    let config = Config::new(vec![ignored.to_str().unwrap().to_string()]).with_parents(true);
+
+    assert_eq!(create_all(&dirs, &config)?, 0);
+    assert!(dirs.iter().all(|dir| dir.is_dir()));
+    assert!(!ignored.exists());
+
+    assert_eq!(create_all([tmp.path().join("x/y")], &Config::default())?, 1);
+    Ok(())
+}
+
+#[test]
+fn config_getters_reflect_setters() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::new(vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(config.dirs(), ["a", "b"]);
+    assert!(config.mode().is_none());
+    assert!(!config.parents());
+    assert!(!config.verbose());
+    assert_eq!(config.mode_format(), ModeFormat::Auto);
+
+    let config = config
+        .with_parents(true)
+        .with_verbosity(2)
+        .with_mode(Mode::from_str("750")?)
+        .with_mode_format(ModeFormat::Octal);
+    assert_eq!(config.mode().map(Mode::to_octal), Some(0o750));
+    assert!(config.parents());
+    assert!(config.verbose());
+    assert_eq!(config.mode_format(), ModeFormat::Octal);
+    Ok(())
+}
+
+/// Delegates to [`RealFs`], failing the calls a test asks it to.
+#[derive(Debug, Default)]
+struct FaultyFs {
+    /// How many `create_dir` calls fail with `EINTR` before they reach the filesystem.
+    interrupts: AtomicUsize,
+    create_dir: Option<io::ErrorKind>,
+    set_permissions: Option<io::ErrorKind>,
+    remove_dir: Option<io::ErrorKind>,
+    free_inodes: Option<u64>,
+}
+
+impl Fs for FaultyFs {
+    fn create_dir(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        let interrupted = self
+            .interrupts
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if interrupted {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        match self.create_dir {
+            Some(kind) => Err(kind.into()),
+            None => RealFs.create_dir(path, mode),
+        }
+    }
+
+    fn set_permissions(&self, path: &Path, perms: fs::Permissions) -> io::Result<()> {
+        match self.set_permissions {
+            Some(kind) => Err(kind.into()),
+            None => RealFs.set_permissions(path, perms),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        RealFs.metadata(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        RealFs.create_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        RealFs.rename(from, to)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        match self.remove_dir {
+            Some(kind) => Err(kind.into()),
+            None => RealFs.remove_dir(path),
+        }
+    }
+
+    fn free_inodes(&self, path: &Path) -> Option<u64> {
+        self.free_inodes.or_else(|| RealFs.free_inodes(path))
+    }
+}
+
+#[test]
+fn interrupted_mkdir_is_retried() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("a");
+    let fs = FaultyFs {
+        interrupts: AtomicUsize::new(2),
+        ..FaultyFs::default()
+    };
+
+    assert_eq!(
+        create_directory_with(&fs, &dir, false, false, false)?,
+        vec![dir.clone()]
+    );
+    assert!(dir.is_dir());
+
+    let fs = FaultyFs {
+        interrupts: AtomicUsize::new(usize::MAX),
+        ..FaultyFs::default()
+    };
+    let result = create_directory_with(&fs, tmp.path().join("b"), false, false, false);
+    assert!(
+        matches!(&result, Err(MkdirrError::Io(e)) if e.kind() == io::ErrorKind::Interrupted),
+        "{result:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn run_reports_chmod_and_mkdir_failures() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let denied = tmp.path().join("denied");
+    let full = tmp.path().join("full");
+
+    let config = Config::new(vec![denied.to_str().unwrap().to_string()])
+        .with_mode(Mode::from_str("700")?)
+        .with_fs(FaultyFs {
+            set_permissions: Some(io::ErrorKind::PermissionDenied),
+            ..FaultyFs::default()
+        });
+    assert_eq!(run(&config)?, 1);
+    assert!(denied.is_dir());
+
+    let config = Config::new(vec![full.to_str().unwrap().to_string()]).with_fs(FaultyFs {
+        create_dir: Some(io::ErrorKind::StorageFull),
+        ..FaultyFs::default()
+    });
+    assert_eq!(run(&config)?, 1);
+    assert!(!full.exists());
+    Ok(())
+}
+
+#[test]
+fn failed_rollback_keeps_the_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = TempDir::new()?;
+    let kept = tmp.path().join("kept");
+    let dirs =
+        [kept.clone(), tmp.path().join("missing/x")].map(|dir| dir.to_str().unwrap().to_string());
+
+    let config = Config::new(dirs.to_vec())
+        .with_transaction(true)
+        .with_quiet(true)
+        .with_fs(FaultyFs {
+            remove_dir: Some(io::ErrorKind::PermissionDenied),
+            ..FaultyFs::default()
+        });
+    assert_eq!(run(&config)?, 1);
+    assert!(kept.is_dir());
+
+    fs::remove_dir(&kept)?;
+    let config = Config::new(dirs.to_vec()).with_transaction(true);
+    assert_eq!(run(&config)?, 1);
+    assert!(!kept.exists());
+    Ok(())
+}
+
+/// The warning goes straight to stderr, so the test runs itself again in a child process,
+/// which does the work when `MKDIRR_CHECK_SPACE_DIR` names the directory to use.
+#[cfg(unix)]
+#[test]
+fn check_space_warns_but_still_creates() -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = env::var_os("MKDIRR_CHECK_SPACE_DIR") {
+        let dir = Path::new(&dir);
+        let dirs = [dir.join("a/b"), dir.join("c")].map(|dir| dir.to_str().unwrap().to_string());
+        let config = Config::new(dirs.to_vec())
+            .with_parents(true)
+            .with_check_space(true)
+            .with_fs(FaultyFs {
+                free_inodes: Some(1),
+                ..FaultyFs::default()
+            });
+        assert_eq!(run(&config)?, 0);
+        return Ok(());
+    }
+
+    let tmp = TempDir::new()?;
+    let output = process::Command::new(env::current_exe()?)
+        .args([
+            "--exact",
+            "check_space_warns_but_still_creates",
+            "--nocapture",
+        ])
+        .env("MKDIRR_CHECK_SPACE_DIR", tmp.path())
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8(output.stderr)?.contains(&format!(
+            "mkdirr: warning: 3 directories to create but only 1 inodes free on the filesystem of '{}'\n",
+            tmp.path().display()
+        )),
+    );
+
+    assert!(tmp.path().join("a/b").is_dir());
+    assert!(tmp.path().join("c").is_dir());
+    Ok(())
+}
+
+/// Stands in for libselinux: every path starts with `default`, and labels are recorded.
+#[derive(Debug)]
+struct FakeSelinux {
+    default: &'static str,
+    labels: Mutex<Vec<String>>,
+}
+
+impl ContextBackend for FakeSelinux {
+    fn set_default(&self, _path: &Path) -> io::Result<()> {
+        self.labels.lock().unwrap().push(self.default.to_string());
+        Ok(())
+    }
+
+    fn get(&self, _path: &Path) -> io::Result<String> {
+        Ok(self
+            .labels
+            .lock()
+            .unwrap()
+            .last()
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn set(&self, _path: &Path, context: &str) -> io::Result<()> {
+        self.labels.lock().unwrap().push(context.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn context_components_replace_parts_of_the_default() -> Result<(), Box<dyn std::error::Error>> {
+    for (components, default, expected) in [
+        (
+            "type=httpd_sys_content_t",
+            "system_u:object_r:default_t:s0",
+            "system_u:object_r:httpd_sys_content_t:s0",
+        ),
+        (
+            "user=staff_u,range=s0:c0.c1023",
+            "system_u:object_r:default_t:s0",
+            "staff_u:object_r:default_t:s0:c0.c1023",
+        ),
+        (
+            "range=s0",
+            "system_u:object_r:default_t",
+            "system_u:object_r:default_t:s0",
+        ),
+    ] {
+        let backend = FakeSelinux {
+            default,
+            labels: Mutex::new(Vec::new()),
+        };
+        components
+            .parse::<Context>()?
+            .apply_with(&backend, Path::new("dir"))?;
+        assert_eq!(
+            backend.labels.lock().unwrap().last().map(String::as_str),
+            Some(expected),
+            "{components}"
+        );
+    }
+
+    assert_eq!("".parse::<Context>()?, Context::Default);
+    assert_eq!(
+        "system_u:object_r:tmp_t:s0".parse::<Context>()?,
+        Context::Explicit("system_u:object_r:tmp_t:s0".to_string())
+    );
+    for invalid in ["level=s0", "type=", "type=a,role"] {
+        assert!(invalid.parse::<Context>().is_err(), "{invalid}");
+    }
+    Ok(())
+}
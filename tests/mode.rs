@@ -0,0 +1,300 @@
+use mkdirr::{Mode, ModeFormat, ModeParseError};
+use std::str::FromStr;
+
+#[test]
+fn display_round_trip() -> Result<(), ModeParseError> {
+    for input in ["u=rwx,g=rx,o=r", "u=w", "g=x,o=rwx", "u=rwx,g+w,o-r"] {
+        let mode = Mode::from_str(input)?;
+        let displayed = mode.to_string();
+        assert_eq!(displayed, input);
+        assert_eq!(Mode::from_str(&displayed)?, mode);
+    }
+    Ok(())
+}
+
+#[test]
+fn display_omits_empty_classes() -> Result<(), ModeParseError> {
+    assert_eq!(Mode::from_str("750")?.to_string(), "u=rwx,g=rx");
+    assert_eq!(Mode::from_str("rx")?.to_string(), "u=rx,g=rx,o=rx");
+    assert_eq!(Mode::from_str("1777")?.to_string(), "u=rwx,g=rwx,o=rwxt");
+    assert_eq!(Mode::from_str("000")?.to_string(), "a=");
+    Ok(())
+}
+
+#[test]
+fn to_octal() -> Result<(), ModeParseError> {
+    for (input, expected) in [
+        ("rwx", 0o777),
+        ("r", 0o444),
+        ("755", 0o755),
+        ("1777", 0o1777),
+        ("u=rwx,g=rx,o=r", 0o754),
+        ("a=r,u+w", 0o644),
+        ("u=rw,u+x,g+r,o-rwx", 0o770),
+        ("u+rwx,g-w,o=r", 0o754),
+    ] {
+        assert_eq!(Mode::from_str(input)?.to_octal(), expected, "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn octal_prefixes() -> Result<(), ModeParseError> {
+    for input in ["755", "0755", "0o755", "0O755", "0o0755"] {
+        assert_eq!(Mode::from_str(input)?.to_octal(), 0o755, "mode {input}");
+    }
+    for input in ["0o1777", "01777", "0o01777"] {
+        assert_eq!(Mode::from_str(input)?.to_octal(), 0o1777, "mode {input}");
+    }
+    assert_eq!(
+        Mode::parse_as("0o700", ModeFormat::Octal)?.to_octal(),
+        0o700
+    );
+    assert!(Mode::parse_as("0o700", ModeFormat::Symbolic).is_err());
+    for input in ["00755", "0o00755", "001777", "0o", "0o789", "0x755", "o755"] {
+        assert!(Mode::from_str(input).is_err(), "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn word_modes() -> Result<(), ModeParseError> {
+    for (input, expected) in [
+        ("user:read,write;group:read", 0o640),
+        ("user:read,write,execute; group:read,execute; other:", 0o750),
+        ("all:read;user:read,write", 0o644),
+        ("user:execute;", 0o100),
+    ] {
+        assert_eq!(Mode::from_str(input)?.to_octal(), expected, "mode {input}");
+    }
+    assert_eq!(
+        Mode::from_str("user:read,write;group:read")?,
+        Mode::from_str("u=rw,g=r")?
+    );
+    for input in ["owner:read", "user:run", "user", ":;"] {
+        assert!(Mode::from_str(input).is_err(), "mode {input}");
+    }
+
+    // The classic notations are unaffected.
+    assert_eq!(Mode::from_str("u=rw,g=r")?.to_octal(), 0o640);
+    assert_eq!(Mode::from_str("rwx")?.to_octal(), 0o777);
+    assert_eq!(Mode::from_str("0755")?.to_octal(), 0o755);
+    Ok(())
+}
+
+#[test]
+fn equal_modes_compare_equal() -> Result<(), ModeParseError> {
+    assert_eq!(Mode::from_str("rw")?, Mode::from_str("wr")?);
+    assert_ne!(Mode::from_str("rw")?, Mode::from_str("rwx")?);
+    Ok(())
+}
+
+#[test]
+fn later_set_clause_replaces_earlier_one() -> Result<(), ModeParseError> {
+    assert_eq!(Mode::from_str("u=r,u=w")?.to_octal(), 0o200);
+    assert_eq!(Mode::from_str("a=rwx,g=r")?.to_octal(), 0o747);
+    assert_eq!(Mode::from_str("u=rwx,g=rx,u=")?.to_octal(), 0o050);
+    assert_eq!(Mode::from_str("u=r,g=w")?.to_octal(), 0o420);
+    assert_eq!(Mode::from_str("=rw")?.to_octal(), 0o666);
+    assert_eq!(Mode::from_str("=rwx,g=r")?.to_octal(), 0o747);
+    Ok(())
+}
+
+#[test]
+fn clause_applies_each_action_in_turn() -> Result<(), ModeParseError> {
+    for (input, base, expected) in [
+        ("u+r-w", 0o244, 0o444),
+        ("u=rw+x", 0o000, 0o700),
+        ("go-w+x,u=r", 0o666, 0o455),
+        ("=r+w", 0o777, 0o666),
+        ("u=r=w", 0o777, 0o200),
+    ] {
+        assert_eq!(
+            Mode::from_str(input)?.resolve(base),
+            expected,
+            "mode {input}"
+        );
+    }
+    assert_eq!(Mode::from_str("u+r-w")?.to_string(), "u+r,u-w");
+    for input in ["u+r-q", "u+r-w-s-t"] {
+        assert!(Mode::from_str(input).is_err(), "mode {input}");
+    }
+    Ok(())
+}
+
+#[test]
+fn from_octal_round_trips() -> Result<(), ModeParseError> {
+    for bits in 0o000..=0o777 {
+        let mode = Mode::from_octal(bits);
+        assert_eq!(mode.to_octal(), bits, "bits {bits:o}");
+        assert_eq!(Mode::from_str(&format!("{bits:o}"))?, mode, "bits {bits:o}");
+    }
+    assert_eq!(Mode::from_octal(0o4755).to_string(), "u=rwxs,g=rx,o=rx");
+    Ok(())
+}
+
+#[test]
+fn parse_as_restricts_notation() -> Result<(), ModeParseError> {
+    assert_eq!(Mode::parse_as("755", ModeFormat::Octal)?.to_octal(), 0o755);
+    assert_eq!(
+        Mode::parse_as("u=rwx", ModeFormat::Symbolic)?.to_octal(),
+        0o700
+    );
+    assert_eq!(Mode::parse_as("7", ModeFormat::Auto)?.to_octal(), 0o007);
+    assert!(Mode::parse_as("u=rwx", ModeFormat::Octal).is_err());
+    assert!(Mode::parse_as("rwx", ModeFormat::Octal).is_err());
+    assert!(Mode::parse_as("755", ModeFormat::Symbolic).is_err());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn apply_to_sets_bits_on_a_directory() -> Result<(), Box<dyn std::error::Error>> {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let tmp = tempfile::TempDir::new()?;
+    let dir = tmp.path().join("dir");
+    fs::create_dir(&dir)?;
+    let current =
+        || -> std::io::Result<u32> { Ok(fs::metadata(&dir)?.permissions().mode() & 0o7777) };
+
+    Mode::from_str("2750")?.apply_to(&dir)?;
+    assert_eq!(current()?, 0o2750);
+
+    Mode::from_str("o+rx,g-w")?.apply_to(&dir)?;
+    assert_eq!(current()?, 0o2755);
+
+    Mode::from_str("u=rwx")?.apply_to(&dir)?;
+    assert_eq!(current()?, 0o700);
+    Ok(())
+}
+
+#[test]
+fn symbolic_special_bits() -> Result<(), ModeParseError> {
+    for (input, expected) in [
+        ("u=rwxs,g=rx,o=rx", 0o4755),
+        ("u=rwx,g=rwxs", 0o2770),
+        ("a=rwx,o=rwxt", 0o1777),
+        ("u=rwxs,u=rwx", 0o700),
+        ("a=rx,u+ws", 0o4755),
+        ("a=rx,g+s", 0o2555),
+        ("a=rx,a+s", 0o6555),
+        ("a=rwx,+t", 0o1777),
+        ("a=rwx,o+t", 0o1777),
+    ] {
+        assert_eq!(Mode::from_str(input)?.to_octal(), expected, "mode {input}");
+    }
+    for input in ["o+s", "o=rs", "u+t", "g=t"] {
+        assert!(Mode::from_str(input).is_err(), "mode {input}");
+    }
+    for (input, displayed) in [
+        ("u=rwxs,g=rx", "u=rwxs,g=rx"),
+        ("u+s,g-s", "u+s,g-s"),
+        ("+t", "a+t"),
+    ] {
+        assert_eq!(Mode::from_str(input)?.to_string(), displayed);
+    }
+    Ok(())
+}
+
+#[test]
+fn resolve_applies_clauses_to_base() -> Result<(), ModeParseError> {
+    for (input, base, expected) in [
+        ("u+x", 0o644, 0o744),
+        ("g-w", 0o775, 0o755),
+        ("u+x,o=", 0o657, 0o750),
+        ("a+rx,u-w", 0o000, 0o555),
+        ("u=rwx,g+s", 0o700, 0o2700),
+        ("o-t", 0o1777, 0o777),
+        ("g+w", 0o4755, 0o4775),
+        ("u=rw,g+w", 0o4755, 0o675),
+        ("750", 0o777, 0o750),
+        ("u=rwx", 0o755, 0o700),
+    ] {
+        assert_eq!(
+            Mode::from_str(input)?.resolve(base),
+            expected,
+            "mode {input} on {base:o}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn conditional_execute_depends_on_the_target() -> Result<(), ModeParseError> {
+    // Directories always get execute from `X`.
+    assert_eq!(Mode::from_str("a+X")?.resolve(0o644), 0o755);
+    assert_eq!(Mode::from_str("a=rX")?.resolve(0o600), 0o555);
+    // In an `=` clause `X` is plain `x`, so the mode stays absolute.
+    assert_eq!(Mode::from_str("go=X")?.to_octal(), 0o011);
+    assert_eq!(Mode::from_str("u=rwX")?, Mode::from_str("u=rwx")?);
+
+    // Files only when some execute bit is already set.
+    let mode = Mode::from_str("a+X")?;
+    assert_eq!(mode.resolve_for(0o644, false), 0o644);
+    assert_eq!(mode.resolve_for(0o744, false), 0o755);
+    assert_eq!(mode.to_string(), "a+X");
+    assert_eq!(Mode::from_str("g-X")?.resolve_for(0o771, false), 0o761);
+    Ok(())
+}
+
+#[test]
+fn parse_errors_have_a_kind() {
+    for (input, expected, message) in [
+        ("", ModeParseError::Empty, "Mode must be defined"),
+        (
+            "u+w,gx",
+            ModeParseError::BadClause {
+                clause: "gx".to_string(),
+                index: 2,
+            },
+            "Invalid permission format: 'gx'",
+        ),
+        ("k+x", ModeParseError::InvalidClass('k'), "Unknown class: k"),
+        (
+            "o+s",
+            ModeParseError::InvalidPerm {
+                clause: "o+s".to_string(),
+                perm: 's',
+                index: 1,
+            },
+            "Invalid permissions in: o+s",
+        ),
+        (
+            "u=rw, g=rq",
+            ModeParseError::InvalidPerm {
+                clause: "g=rq".to_string(),
+                perm: 'q',
+                index: 2,
+            },
+            "Invalid permissions in: g=rq",
+        ),
+        (
+            "user:read;;group",
+            ModeParseError::BadClause {
+                clause: "group".to_string(),
+                index: 3,
+            },
+            "Invalid permission format: 'group'",
+        ),
+        (
+            "q=rw",
+            ModeParseError::UnknownClassOrPerm("q=rw".to_string()),
+            "Unknown class or perm: q=rw",
+        ),
+        (
+            "789",
+            ModeParseError::InvalidMode("789".to_string()),
+            "Invalid mode: 789",
+        ),
+    ] {
+        let err = Mode::from_str(input).unwrap_err();
+        assert_eq!(err, expected, "mode {input:?}");
+        assert_eq!(err.to_string(), message, "mode {input:?}");
+    }
+
+    assert_eq!(
+        Mode::parse_as("rwx", ModeFormat::Octal).unwrap_err(),
+        ModeParseError::ExpectedOctal("rwx".to_string())
+    );
+}